@@ -0,0 +1,195 @@
+use crate::error::Result;
+
+use git2::{ObjectType, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use gix_glob::{Pattern, pattern::Case, wildmatch::Mode};
+
+/// Attributes that exclude a file from the HoC when they are set.
+///
+/// `linguist-generated` and `linguist-vendored` are also used by GitHub to hide files from diffs
+/// and language statistics, `hoc-exclude` only affects the HoC.
+const EXCLUDING: &[&str] = &["linguist-generated", "linguist-vendored", "hoc-exclude"];
+
+/// Collect the exclusion rules of all `.gitattributes` files in `tree`.
+///
+/// Each rule is a normalized `.gitattributes` line with a repository relative pattern and a single
+/// attribute, e.g. `vendor/** linguist-vendored` or `vendor/own/** -linguist-vendored`. Rules of
+/// nested files come after the rules of their parents, so the last matching rule wins.
+pub(crate) fn rules(repo: &Repository, tree: &Tree<'_>) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.name_bytes() == b".gitattributes" && entry.kind() == Some(ObjectType::Blob) {
+            files.push((dir.to_string(), entry.id()));
+        }
+        TreeWalkResult::Ok
+    })?;
+
+    let mut rules = Vec::new();
+    for (dir, id) in files {
+        let blob = repo.find_blob(id)?;
+        parse(&dir, &String::from_utf8_lossy(blob.content()), &mut rules);
+    }
+    Ok(rules)
+}
+
+/// Parse a `.gitattributes` file in `dir` (empty for the root or ending with `/`).
+fn parse(dir: &str, content: &str, rules: &mut Vec<String>) {
+    for line in content.lines() {
+        let mut tokens = line.split_whitespace();
+        let Some(pattern) = tokens.next() else {
+            continue;
+        };
+        // comments, macro definitions and quoted patterns are not supported
+        if pattern.starts_with(['#', '[', '"']) {
+            continue;
+        }
+        let pattern = if dir.is_empty() {
+            pattern.to_string()
+        } else if pattern.trim_end_matches('/').contains('/') {
+            format!("{dir}{}", pattern.trim_start_matches('/'))
+        } else {
+            format!("{dir}**/{pattern}")
+        };
+        for attribute in tokens {
+            if let Some((name, excluded)) = normalize(attribute) {
+                let sign = if excluded { "" } else { "-" };
+                rules.push(format!("{pattern} {sign}{name}"));
+            }
+        }
+    }
+}
+
+/// Map an attribute assignment to the attribute and whether it excludes the file.
+///
+/// `-diff` and the `binary` macro are treated like `linguist-generated`, since the lines of such
+/// files are not meant to be read.
+fn normalize(attribute: &str) -> Option<(&'static str, bool)> {
+    match attribute {
+        "-diff" | "binary" => return Some(("linguist-generated", true)),
+        "diff" => return Some(("linguist-generated", false)),
+        _ => {}
+    }
+    let (name, excluded) = if let Some(name) = attribute
+        .strip_prefix('-')
+        .or_else(|| attribute.strip_prefix('!'))
+    {
+        (name, false)
+    } else if let Some((name, value)) = attribute.split_once('=') {
+        (name, value != "false")
+    } else {
+        (attribute, true)
+    };
+    EXCLUDING
+        .iter()
+        .find(|known| **known == name)
+        .map(|known| (*known, excluded))
+}
+
+/// Compiled exclusion rules.
+pub(crate) struct Attributes {
+    rules: Vec<(Pattern, usize, bool)>,
+}
+
+impl Attributes {
+    pub(crate) fn new(rules: &[String]) -> Self {
+        let rules = rules
+            .iter()
+            .filter_map(|rule| {
+                let (pattern, attribute) = rule.rsplit_once(' ')?;
+                let (name, excluded) = attribute
+                    .strip_prefix('-')
+                    .map_or((attribute, true), |name| (name, false));
+                let idx = EXCLUDING.iter().position(|known| *known == name)?;
+                Some((
+                    Pattern::from_bytes_without_negation(pattern.as_bytes())?,
+                    idx,
+                    excluded,
+                ))
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Check if any excluding attribute is set for `path`.
+    pub(crate) fn excluded(&self, path: &str) -> bool {
+        if path.is_empty() || self.rules.is_empty() {
+            return false;
+        }
+        let basename = path.rfind('/').map(|pos| pos + 1);
+        let mut state = [false; EXCLUDING.len()];
+        for (pattern, idx, excluded) in &self.rules {
+            if pattern.matches_repo_relative_path(
+                path.into(),
+                basename,
+                Some(false),
+                Case::Sensitive,
+                Mode::NO_MATCH_SLASH_LITERAL,
+            ) {
+                state[*idx] = *excluded;
+            }
+        }
+        state.into_iter().any(|excluded| excluded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Attributes, parse};
+
+    fn rules(files: &[(&str, &str)]) -> Vec<String> {
+        let mut rules = Vec::new();
+        for (dir, content) in files {
+            parse(dir, content, &mut rules);
+        }
+        rules
+    }
+
+    #[test]
+    fn parse_rules() {
+        let rules = rules(&[
+            (
+                "",
+                "# comment\n*.min.js linguist-generated\nvendor/** linguist-vendored=true text\n*.svg -diff\n",
+            ),
+            (
+                "docs/",
+                "*.md hoc-exclude\napi/*.json -linguist-generated\n",
+            ),
+        ]);
+        assert_eq!(
+            rules,
+            vec![
+                "*.min.js linguist-generated",
+                "vendor/** linguist-vendored",
+                "*.svg linguist-generated",
+                "docs/**/*.md hoc-exclude",
+                "docs/api/*.json -linguist-generated",
+            ]
+        );
+    }
+
+    #[test]
+    fn excluded_paths() {
+        let attributes = Attributes::new(&rules(&[
+            (
+                "",
+                "*.gen.rs linguist-generated\nvendor/** linguist-vendored\nvendor/own/** linguist-vendored=false\n",
+            ),
+            ("docs/", "*.md hoc-exclude\n"),
+        ]));
+        assert!(attributes.excluded("src/model.gen.rs"));
+        assert!(attributes.excluded("vendor/lib/a.c"));
+        assert!(!attributes.excluded("vendor/own/a.c"));
+        assert!(attributes.excluded("docs/guide/intro.md"));
+        assert!(!attributes.excluded("README.md"));
+        assert!(!attributes.excluded("src/main.rs"));
+    }
+
+    #[test]
+    fn attributes_are_independent() {
+        let attributes = Attributes::new(&rules(&[(
+            "",
+            "generated/** linguist-generated\ngenerated/** -linguist-vendored\n",
+        )]));
+        assert!(attributes.excluded("generated/a.rs"));
+    }
+}
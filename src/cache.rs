@@ -1,17 +1,22 @@
 use crate::{
     config::Settings,
+    diff::DiffSettings,
     error::{Error, Result},
     platform::Platform,
+    repo_id::RepoId,
 };
 
 use std::{
-    collections::BTreeSet,
-    fs::{OpenOptions, create_dir_all, remove_dir_all},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{OpenOptions, create_dir_all, remove_dir_all, remove_file},
+    hash::{Hash, Hasher},
     io::{self, BufReader},
     path::PathBuf,
+    sync::Arc,
 };
 
 use dashmap::DashMap;
+use jiff::{SignedDuration, Timestamp};
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, trace};
 
@@ -19,15 +24,31 @@ pub(crate) trait Cache<K, V> {
     fn load(&self, key: &K) -> Result<Option<V>>;
     fn store(&self, key: K, value: V) -> Result<()>;
 
-    fn clear(&self, platform: Platform, owner: &str, repo: &str) -> Result<()>;
+    fn clear(&self, id: &RepoId) -> Result<()>;
+
+    /// Remove all entries for the default branch of a repository, e.g. because the default branch
+    /// changed.
+    fn clear_default_branch(&self, id: &RepoId) -> Result<()>;
 }
 
+/// Cache key used for requests without an explicit branch.
+// TODO: lets hope no branch by that name exists...
+const DEFAULT_BRANCH_KEY: &str = "default_branch";
+
+/// Branch name to count the union of all branches. Not a valid branch name in git.
+pub(crate) const ALL_BRANCHES: &str = "*";
+
+/// Cache key used for requests for [`ALL_BRANCHES`].
+const ALL_BRANCHES_KEY: &str = "all_branches";
+
 trait ToQuery {
     fn to_query(&self) -> String;
 }
 
 pub(crate) type Excludes = BTreeSet<String>;
 
+pub(crate) type Includes = BTreeSet<String>;
+
 impl ToQuery for Excludes {
     fn to_query(&self) -> String {
         let excludes: Vec<_> = self.iter().map(AsRef::as_ref).collect();
@@ -37,77 +58,191 @@ impl ToQuery for Excludes {
     }
 }
 
-#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+/// Options that change which lines are counted. Each combination is cached separately.
+#[derive(Hash, Eq, PartialEq, Clone, Debug, Default)]
+pub(crate) struct CountOptions {
+    pub(crate) excludes: Excludes,
+    /// Only count files matching any of these patterns, if not empty
+    pub(crate) includes: Includes,
+    /// Count files that are excluded by `.gitattributes`
+    pub(crate) ignore_attributes: bool,
+    /// Only count commits that are not reachable from this revision
+    pub(crate) from: Option<String>,
+    /// Count the commits reachable from this revision instead of the branch
+    pub(crate) to: Option<String>,
+    /// Whitespace, rename and merge handling
+    pub(crate) diff: DiffSettings,
+    /// Add the HoC of submodules over their pinned commit ranges
+    pub(crate) recurse_submodules: bool,
+}
+
+impl CountOptions {
+    /// Directory of the cache entry below the branch directory.
+    ///
+    /// Only options that differ from the default are added to the encoded excludes, so entries
+    /// written by older versions keep their location.
+    fn cache_key(&self) -> String {
+        let mut key = self.excludes.to_query();
+        if !self.includes.is_empty() {
+            key.push_str("&include=");
+            key.push_str(&self.includes.to_query());
+        }
+        if self.ignore_attributes {
+            key.push_str("&attributes=false");
+        }
+        if let Some(from) = &self.from {
+            key.push_str("&from=");
+            key.push_str(&urlencoding::encode(from));
+        }
+        if let Some(to) = &self.to {
+            key.push_str("&to=");
+            key.push_str(&urlencoding::encode(to));
+        }
+        key.push_str(&self.diff.cache_key());
+        if self.recurse_submodules {
+            key.push_str("&submodules=recurse");
+        }
+        key
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct HocParams {
-    pub(crate) platform: Platform,
-    pub(crate) owner: String,
-    pub(crate) repo: String,
+    pub(crate) id: RepoId,
     pub(crate) branch: Option<String>,
-    pub(crate) excludes: Excludes,
+    pub(crate) options: CountOptions,
+    /// Keep the [`Details`] of the cache entry. Not part of the cache key
+    pub(crate) details: bool,
+}
+
+// `details` is left out, so a queued job is not calculated a second time with or without details,
+// which would race on the same cache entry
+impl PartialEq for HocParams {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.branch == other.branch && self.options == other.options
+    }
+}
+
+impl Eq for HocParams {}
+
+impl Hash for HocParams {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.branch.hash(state);
+        self.options.hash(state);
+    }
 }
 
 impl HocParams {
     pub(crate) fn new(
-        platform: Platform,
-        owner: String,
-        repo: String,
+        id: RepoId,
         branch: impl Into<Option<String>>,
-        excludes: Excludes,
+        options: CountOptions,
     ) -> Self {
         Self {
-            platform,
-            owner,
-            repo,
+            id,
             branch: branch.into(),
-            excludes,
+            options,
+            details: false,
         }
     }
 
+    /// Request the [`Details`] of the entry, e.g. for the authors endpoint or a time window.
+    pub(crate) fn with_details(mut self) -> Self {
+        self.details = true;
+        self
+    }
+
+    /// Count the commits reachable from any branch.
+    pub(crate) fn all_branches(&self) -> bool {
+        self.branch.as_deref() == Some(ALL_BRANCHES)
+    }
+
     fn cache_branch_name(&self) -> &str {
-        self.branch
-            .as_deref()
-            // TODO: lets hope no branch by that name exists...
-            .unwrap_or("default_branch")
+        match self.branch.as_deref() {
+            None => DEFAULT_BRANCH_KEY,
+            Some(ALL_BRANCHES) => ALL_BRANCHES_KEY,
+            Some(branch) => branch,
+        }
     }
 
     fn cache_file(&self, settings: &Settings) -> PathBuf {
-        let excludes = self.excludes.to_query();
-
-        settings
-            .cachedir
-            .join(self.platform.domain())
-            .join(self.owner.to_lowercase().as_str())
-            .join(self.repo.to_lowercase().as_str())
+        self.id
+            .path(&settings.cachedir)
             .join(self.cache_branch_name())
-            .join(excludes.as_str())
+            .join(self.options.cache_key())
             .join("cache")
             .with_extension("json")
     }
 
+    /// File of the [`CommitStore`](crate::store::CommitStore) of the repository.
+    pub(crate) fn numstat_file(&self, settings: &Settings) -> PathBuf {
+        self.id
+            .path(&settings.cachedir)
+            .join(self.options.diff.store_name())
+            .with_extension("jsonl")
+    }
+
     pub(crate) fn repo(&self, settings: &Settings) -> PathBuf {
-        settings
-            .repodir
-            .join(self.platform.domain())
-            .join(self.owner.to_lowercase().as_str())
-            .join(self.repo.to_lowercase().as_str())
+        if *self.id.platform() == Platform::Local {
+            return settings.local_repo(&self.id);
+        }
+        self.id.path(&settings.repodir)
     }
 
     pub(crate) fn url(&self) -> String {
-        format!("https://{}/{}", self.platform.domain(), self.slug())
+        self.id
+            .platform()
+            .clone_url(self.id.owner(), self.id.repo())
+    }
+
+    pub(crate) fn web_url(&self) -> String {
+        self.id.platform().web_url(self.id.owner(), self.id.repo())
     }
 
     pub(crate) fn service_path(&self) -> String {
-        format!("{}/{}", self.platform.url_path(), self.slug())
+        let platform = self.id.platform();
+        if matches!(platform, Platform::Git(_)) {
+            // the remote is passed using the `url` query parameter
+            platform.url_path().to_string()
+        } else {
+            format!("{}/{}", platform.url_path(), self.slug())
+        }
     }
 
     fn slug(&self) -> String {
-        format!("{}/{}", self.owner, self.repo)
+        format!("{}/{}", self.id.owner(), self.id.repo())
+    }
+}
+
+/// Time after which an alias is checked again, since a new repository might have been created at
+/// the old location.
+const ALIAS_TTL: SignedDuration = SignedDuration::from_hours(24);
+
+/// Canonical location of a repository that was renamed or transferred.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Alias {
+    pub(crate) owner: String,
+    pub(crate) repo: String,
+    /// Unix timestamp of the last time the remote redirected to the canonical location. Missing in
+    /// aliases of older versions, so they are checked again
+    #[serde(default)]
+    pub(crate) checked: i64,
+}
+
+impl Alias {
+    fn is_fresh(&self) -> bool {
+        Timestamp::from_second(self.checked)
+            .is_ok_and(|checked| checked + ALIAS_TTL > Timestamp::now())
     }
 }
 
 pub struct Persist {
     in_memory: InMemoryCache,
     disk: DiskCache,
+    /// Known aliases. `None` is stored for repositories without an alias, so the disk is not
+    /// checked on every request.
+    aliases: DashMap<RepoId, Option<Alias>>,
 }
 
 impl Persist {
@@ -116,36 +251,71 @@ impl Persist {
         Self {
             in_memory: InMemoryCache::new(),
             disk: DiskCache { settings },
+            aliases: DashMap::new(),
         }
     }
+
+    fn alias(&self, id: &RepoId) -> Result<Option<Alias>> {
+        if let Some(alias) = self.aliases.get(id) {
+            return Ok(alias.value().clone());
+        }
+        let alias = self.disk.load_alias(id)?;
+        self.aliases.insert(id.clone(), alias.clone());
+        Ok(alias)
+    }
+
+    /// Follow the recorded aliases of a repository to its canonical id.
+    ///
+    /// Expired aliases are not followed, so the next calculation asks the remote again, whether the
+    /// repository still redirects.
+    pub(crate) fn resolve_alias(&self, mut id: RepoId) -> Result<RepoId> {
+        // aliases never point to a repository that has an alias itself (see `store_alias`), so
+        // this terminates
+        while let Some(alias) = self.alias(&id)?.filter(Alias::is_fresh) {
+            id = id.renamed(alias.owner, alias.repo)?;
+        }
+        Ok(id)
+    }
+
+    /// Record that the repository `id` moved to `canonical`.
+    pub(crate) fn store_alias(&self, id: &RepoId, canonical: &RepoId) -> Result<()> {
+        // the target is canonical now, e.g. if a repository was renamed back and forth
+        self.disk.remove_alias(canonical)?;
+        self.aliases.insert(canonical.clone(), None);
+
+        let alias = Alias {
+            owner: canonical.owner().to_string(),
+            repo: canonical.repo().to_string(),
+            checked: Timestamp::now().as_second(),
+        };
+        self.disk.store_alias(id, &alias)?;
+        self.aliases.insert(id.clone(), Some(alias));
+        Ok(())
+    }
+
+    /// Forget that the repository `id` moved, e.g. because a new repository was created at its
+    /// location.
+    pub(crate) fn remove_alias(&self, id: &RepoId) -> Result<()> {
+        self.disk.remove_alias(id)?;
+        self.aliases.insert(id.clone(), None);
+        Ok(())
+    }
 }
 
 impl Drop for Persist {
     fn drop(&mut self) {
         info!("persisting cache");
         for r in &self.in_memory.cache {
-            let platform = *r.key();
+            let id = r.key();
             for r in r.value() {
-                let owner = r.key();
+                let branch = r.key();
                 for r in r.value() {
-                    let repo = r.key();
-                    for r in r.value() {
-                        let branch = r.key();
-                        for r in r.value() {
-                            let excludes = r.key().clone();
-                            let key = HocParams::new(
-                                platform,
-                                owner.clone(),
-                                repo.clone(),
-                                branch.clone(),
-                                excludes,
-                            );
-                            if let Err(err) = self.disk.store(key, r.value().clone()) {
-                                error!(%err, key = ?r.key(), "cannot write cache to disk");
-                            } else {
-                                trace!(key = ?r.key(), "persisted");
-                            }
-                        }
+                    let options = r.key().clone();
+                    let key = HocParams::new(id.clone(), branch.clone(), options);
+                    if let Err(err) = self.disk.store(key, r.value().clone()) {
+                        error!(%err, key = ?r.key(), "cannot write cache to disk");
+                    } else {
+                        trace!(key = ?r.key(), "persisted");
                     }
                 }
             }
@@ -169,9 +339,23 @@ impl Cache<HocParams, CacheEntry> for Persist {
         self.in_memory.store(key, value)
     }
 
-    fn clear(&self, platform: Platform, owner: &str, repo: &str) -> Result<()> {
-        let im_res = self.in_memory.clear(platform, owner, repo);
-        let disk_res = self.disk.clear(platform, owner, repo);
+    fn clear(&self, id: &RepoId) -> Result<()> {
+        // the alias is stored in the cache directory of the repository
+        self.aliases.remove(id);
+        let im_res = self.in_memory.clear(id);
+        let disk_res = self.disk.clear(id);
+        if let Err(e) = im_res {
+            Err(e)?
+        } else if let Err(e) = disk_res {
+            Err(e)?
+        } else {
+            Ok(())
+        }
+    }
+
+    fn clear_default_branch(&self, id: &RepoId) -> Result<()> {
+        let im_res = self.in_memory.clear_default_branch(id);
+        let disk_res = self.disk.clear_default_branch(id);
         if let Err(e) = im_res {
             Err(e)?
         } else if let Err(e) = disk_res {
@@ -184,10 +368,7 @@ impl Cache<HocParams, CacheEntry> for Persist {
 
 struct InMemoryCache {
     #[allow(clippy::type_complexity)]
-    cache: DashMap<
-        Platform,
-        DashMap<String, DashMap<String, DashMap<String, DashMap<Excludes, CacheEntry>>>>,
-    >,
+    cache: DashMap<RepoId, DashMap<String, DashMap<CountOptions, CacheEntry>>>,
 }
 
 impl InMemoryCache {
@@ -202,34 +383,29 @@ impl Cache<HocParams, CacheEntry> for InMemoryCache {
     fn store(&self, key: HocParams, value: CacheEntry) -> Result<()> {
         let branch_key = key.cache_branch_name().to_string();
         self.cache
-            .entry(key.platform)
-            .or_default()
-            .entry(key.owner)
-            .or_default()
-            .entry(key.repo)
+            .entry(key.id)
             .or_default()
             .entry(branch_key)
             .or_default()
-            .insert(key.excludes, value);
+            .insert(key.options, value);
         Ok(())
     }
 
     fn load(&self, key: &HocParams) -> Result<Option<CacheEntry>> {
-        Ok(self.cache.get(&key.platform).and_then(|c| {
-            c.get(&key.owner).and_then(|c| {
-                c.get(&key.repo).and_then(|c| {
-                    c.get(key.cache_branch_name())
-                        .and_then(|c| c.get(&key.excludes).map(|r| r.value().clone()))
-                })
-            })
+        Ok(self.cache.get(&key.id).and_then(|c| {
+            c.get(key.cache_branch_name())
+                .and_then(|c| c.get(&key.options).map(|r| r.value().clone()))
         }))
     }
 
-    fn clear(&self, platform: Platform, owner: &str, repo: &str) -> Result<()> {
-        if let Some(c) = self.cache.get(&platform)
-            && let Some(c) = c.value().get(owner)
-        {
-            c.value().remove(repo);
+    fn clear(&self, id: &RepoId) -> Result<()> {
+        self.cache.remove(id);
+        Ok(())
+    }
+
+    fn clear_default_branch(&self, id: &RepoId) -> Result<()> {
+        if let Some(c) = self.cache.get(id) {
+            c.value().remove(DEFAULT_BRANCH_KEY);
         }
         Ok(())
     }
@@ -239,11 +415,74 @@ struct DiskCache {
     settings: Settings,
 }
 
+impl DiskCache {
+    fn alias_file(&self, id: &RepoId) -> PathBuf {
+        id.path(&self.settings.cachedir)
+            .join("alias")
+            .with_extension("json")
+    }
+
+    fn load_alias(&self, id: &RepoId) -> Result<Option<Alias>> {
+        match OpenOptions::new().read(true).open(self.alias_file(id)) {
+            Ok(f) => Ok(Some(serde_json::from_reader(BufReader::new(f))?)),
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    Ok(None)
+                } else {
+                    Err(e)?
+                }
+            }
+        }
+    }
+
+    fn store_alias(&self, id: &RepoId, alias: &Alias) -> Result<()> {
+        let alias_file = self.alias_file(id);
+
+        let parent = alias_file.parent().ok_or(Error::Internal)?;
+        create_dir_all(parent)?;
+
+        serde_json::to_writer(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(alias_file)?,
+            alias,
+        )?;
+        Ok(())
+    }
+
+    fn remove_alias(&self, id: &RepoId) -> Result<()> {
+        remove_file(self.alias_file(id)).or_else(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        })?;
+        Ok(())
+    }
+}
+
 impl Cache<HocParams, CacheEntry> for DiskCache {
     fn load(&self, key: &HocParams) -> Result<Option<CacheEntry>> {
         let cache_file = key.cache_file(&self.settings);
-        match OpenOptions::new().read(true).open(&cache_file) {
-            Ok(f) => Ok(serde_json::from_reader(BufReader::new(f))?),
+        match std::fs::read(&cache_file) {
+            Ok(content) => match serde_json::from_slice(&content) {
+                Ok(entry) => Ok(Some(entry)),
+                Err(err) if err.is_data() => {
+                    // written by a version before additions and deletions were stored separately.
+                    // It is served until it is recalculated and overwritten.
+                    if let Ok(entry) = serde_json::from_slice::<LegacyEntry>(&content) {
+                        info!(path = %cache_file.display(), "migrating outdated cache entry");
+                        Ok(Some(entry.into()))
+                    } else {
+                        info!(%err, path = %cache_file.display(), "ignoring invalid cache entry");
+                        Ok(None)
+                    }
+                }
+                Err(err) => Err(err)?,
+            },
             Err(e) => {
                 if e.kind() == io::ErrorKind::NotFound {
                     Ok(None)
@@ -273,14 +512,19 @@ impl Cache<HocParams, CacheEntry> for DiskCache {
         Ok(())
     }
 
-    fn clear(&self, platform: Platform, owner: &str, repo: &str) -> Result<()> {
-        let cache_dir = self
-            .settings
-            .cachedir
-            .join(platform.domain())
-            .join(owner)
-            .join(repo);
-        remove_dir_all(cache_dir).or_else(|e| {
+    fn clear(&self, id: &RepoId) -> Result<()> {
+        remove_dir_all(id.path(&self.settings.cachedir)).or_else(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        })?;
+        Ok(())
+    }
+
+    fn clear_default_branch(&self, id: &RepoId) -> Result<()> {
+        remove_dir_all(id.path(&self.settings.cachedir).join(DEFAULT_BRANCH_KEY)).or_else(|e| {
             if e.kind() == io::ErrorKind::NotFound {
                 Ok(())
             } else {
@@ -291,36 +535,418 @@ impl Cache<HocParams, CacheEntry> for DiskCache {
     }
 }
 
+/// Contributions of a single author.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Author {
+    pub(crate) name: String,
+    pub(crate) email: String,
+    /// Number of added lines
+    pub(crate) additions: u64,
+    /// Number of deleted lines
+    pub(crate) deletions: u64,
+    /// Number of commits
+    pub(crate) commits: u64,
+}
+
+impl Author {
+    pub(crate) fn new(name: impl Into<String>, email: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            email: email.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Hits of Code of the author
+    pub(crate) fn hoc(&self) -> u64 {
+        self.additions.saturating_add(self.deletions)
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.additions = self.additions.saturating_add(other.additions);
+        self.deletions = self.deletions.saturating_add(other.deletions);
+        self.commits += other.commits;
+    }
+}
+
+/// Added and deleted lines of a file.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Lines {
+    pub(crate) additions: u64,
+    pub(crate) deletions: u64,
+}
+
+impl Lines {
+    pub(crate) fn add(&mut self, other: Self) {
+        self.additions = self.additions.saturating_add(other.additions);
+        self.deletions = self.deletions.saturating_add(other.deletions);
+    }
+}
+
+/// HoC of a submodule, included in the HoC of the repository.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SubmoduleStats {
+    /// Path of the submodule in the repository
+    pub(crate) path: String,
+    /// Remote URL from `.gitmodules`
+    pub(crate) url: String,
+    /// Commit the submodule was counted up to
+    pub(crate) head: String,
+    pub(crate) additions: u64,
+    pub(crate) deletions: u64,
+    pub(crate) commits: u64,
+}
+
+/// Added and deleted lines of a single commit.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct TimelineEntry {
+    /// Commit time in seconds since the epoch
+    pub(crate) time: i64,
+    pub(crate) additions: u64,
+    pub(crate) deletions: u64,
+    /// Number of changed binary files
+    #[serde(default)]
+    pub(crate) binary_changes: u64,
+}
+
+/// Changes counted since the cached head.
+#[derive(Default, Debug)]
+pub(crate) struct Changes {
+    pub(crate) additions: u64,
+    pub(crate) deletions: u64,
+    pub(crate) commits: u64,
+    /// Number of changed binary files
+    pub(crate) binary_changes: u64,
+    /// Authors, files and timeline of the counted commits, if the entry keeps them
+    pub(crate) details: Option<Details>,
+    /// Exclusion rules from `.gitattributes` at the new head
+    pub(crate) attributes: Vec<String>,
+    /// Resolved `from` revision of the counted range
+    pub(crate) base: Option<String>,
+    /// Lines counted in submodules, included in the totals above
+    pub(crate) submodules: Vec<SubmoduleStats>,
+    /// Heads of all branches, if all branches are counted
+    pub(crate) branches: Vec<String>,
+}
+
+/// Contributions per author and lines per file and commit of a cache entry.
+///
+/// Only needed by the authors, tree, languages and history endpoints, the overview page and counts
+/// in a time window, so it is shared instead of copied whenever an entry is loaded. The details
+/// grow with the history, so they are only kept for entries that were requested by one of these.
+/// Other entries can derive them from the [`CommitStore`](crate::store::CommitStore) without
+/// diffing the history again.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub(crate) struct Details {
+    /// Contributions per author identity, as recorded in the commits
+    pub(crate) identities: Vec<Author>,
+    /// `identities` merged through the `.mailmap`, sorted by HoC
+    pub(crate) authors: Vec<Author>,
+    /// Added and deleted lines per file path
+    pub(crate) files: BTreeMap<String, Lines>,
+    /// Lines per commit, to count the lines in a time window
+    pub(crate) timeline: Vec<TimelineEntry>,
+}
+
+impl Details {
+    fn add(&mut self, other: Self) {
+        let mut index: HashMap<_, _> = self
+            .identities
+            .iter()
+            .enumerate()
+            .map(|(idx, old)| ((old.name.clone(), old.email.clone()), idx))
+            .collect();
+        for identity in other.identities {
+            let key = (identity.name.clone(), identity.email.clone());
+            if let Some(&idx) = index.get(&key) {
+                self.identities[idx].add(&identity);
+            } else {
+                index.insert(key, self.identities.len());
+                self.identities.push(identity);
+            }
+        }
+        self.timeline.extend(other.timeline);
+        for (path, lines) in other.files {
+            self.files.entry(path).or_default().add(lines);
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) enum CacheEntry {
     Cached {
         /// HEAD commit ref
         head: String,
-        /// `HoC` value
-        count: u64,
+        /// Number of added lines
+        additions: u64,
+        /// Number of deleted lines
+        deletions: u64,
         /// Number of commits
         commits: u64,
+        // Fields added later must be optional, so new versions keep the entries of older ones.
+        /// Number of changed binary files
+        #[serde(default)]
+        binary_changes: u64,
+        /// Authors, files and timeline, stored in the same object as the other fields. `None`
+        /// if they were never requested
+        #[serde(flatten)]
+        details: Option<Arc<Details>>,
+        /// Exclusion rules from `.gitattributes` the entry was counted with
+        #[serde(default)]
+        attributes: Vec<String>,
+        /// Commit the counted range starts at, if `from` was passed
+        #[serde(default)]
+        base: Option<String>,
+        /// HoC per submodule, if submodules are counted
+        #[serde(default)]
+        submodules: Vec<SubmoduleStats>,
+        /// Heads of all branches the entry was counted up to, if all branches are counted
+        #[serde(default)]
+        branches: Vec<String>,
     },
     NotFound,
+    /// The configured credentials were rejected
+    Unauthorized,
+    /// Entry of a version before additions and deletions were stored separately. Its HoC is
+    /// served until the repository is counted again
+    Outdated {
+        head: String,
+        count: u64,
+        commits: u64,
+    },
+}
+
+/// Cache entry as written before additions and deletions were stored separately.
+#[derive(Deserialize)]
+enum LegacyEntry {
+    Cached {
+        head: String,
+        count: u64,
+        commits: u64,
+    },
+}
+
+impl From<LegacyEntry> for CacheEntry {
+    fn from(entry: LegacyEntry) -> Self {
+        let LegacyEntry::Cached {
+            head,
+            count,
+            commits,
+        } = entry;
+        Self::Outdated {
+            head,
+            count,
+            commits,
+        }
+    }
 }
 
 impl CacheEntry {
-    pub(crate) fn update(self, count: u64, commits: u64, head: &str) -> Self {
+    pub(crate) fn update(self, changes: Changes, head: &str) -> Self {
+        let Changes {
+            additions,
+            deletions,
+            commits,
+            binary_changes,
+            details: new_details,
+            attributes,
+            base,
+            submodules,
+            branches,
+        } = changes;
         match self {
-            Self::NotFound => Self::Cached {
-                count,
+            Self::NotFound | Self::Unauthorized | Self::Outdated { .. } => Self::Cached {
+                additions,
+                deletions,
                 commits,
+                binary_changes,
+                details: new_details.map(Arc::new),
+                attributes,
+                base,
+                submodules,
+                branches,
                 head: head.to_string(),
             },
             Self::Cached {
-                count: old_count,
+                additions: old_additions,
+                deletions: old_deletions,
                 commits: old_commits,
-                head,
-            } => Self::Cached {
-                count: old_count + count,
-                commits: old_commits + commits,
-                head,
-            },
+                binary_changes: old_binary_changes,
+                details,
+                submodules: mut old_submodules,
+                ..
+            } => {
+                // the details of an entry are either kept since it was created, or not at all
+                let details = details.zip(new_details).map(|(details, new_details)| {
+                    let mut details = Arc::unwrap_or_clone(details);
+                    details.add(new_details);
+                    Arc::new(details)
+                });
+                for submodule in submodules {
+                    if let Some(old) = old_submodules.iter_mut().find(|s| s.path == submodule.path)
+                    {
+                        old.additions = old.additions.saturating_add(submodule.additions);
+                        old.deletions = old.deletions.saturating_add(submodule.deletions);
+                        old.commits += submodule.commits;
+                        old.url = submodule.url;
+                        old.head = submodule.head;
+                    } else {
+                        old_submodules.push(submodule);
+                    }
+                }
+                Self::Cached {
+                    additions: old_additions.saturating_add(additions),
+                    deletions: old_deletions.saturating_add(deletions),
+                    commits: old_commits + commits,
+                    binary_changes: old_binary_changes + binary_changes,
+                    details,
+                    attributes,
+                    base,
+                    submodules: old_submodules,
+                    branches,
+                    head: head.to_string(),
+                }
+            }
+        }
+    }
+
+    /// Merge the author identities into authors.
+    ///
+    /// `resolve` maps an identity to the canonical name and email, e.g. using the `.mailmap`.
+    /// Identities with the same canonical email (ignoring the case) are the same author.
+    pub(crate) fn resolve_authors(
+        mut self,
+        resolve: impl Fn(&str, &str) -> (String, String),
+    ) -> Self {
+        if let Self::Cached {
+            details: Some(details),
+            ..
+        } = &mut self
+        {
+            let Details {
+                identities,
+                authors,
+                ..
+            } = Arc::make_mut(details);
+            let mut resolved: Vec<Author> = Vec::new();
+            let mut index = HashMap::new();
+            for identity in identities.iter() {
+                let (name, email) = resolve(&identity.name, &identity.email);
+                let key = if email.is_empty() { &name } else { &email }.to_lowercase();
+                let idx = *index.entry(key).or_insert_with(|| {
+                    resolved.push(Author::new(name, email));
+                    resolved.len() - 1
+                });
+                resolved[idx].add(identity);
+            }
+            resolved.sort_by(|a, b| b.hoc().cmp(&a.hoc()).then_with(|| a.name.cmp(&b.name)));
+            *authors = resolved;
         }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Cache, CacheEntry, Changes, CountOptions, Details, HocParams, InMemoryCache, Lines,
+    };
+    use crate::{platform::Platform, repo_id::RepoId, worker::Queue};
+
+    use std::sync::Arc;
+
+    fn entry() -> CacheEntry {
+        let changes = Changes {
+            additions: 3,
+            details: Some(Details {
+                files: [(
+                    "a.txt".to_string(),
+                    Lines {
+                        additions: 3,
+                        deletions: 0,
+                    },
+                )]
+                .into(),
+                ..Details::default()
+            }),
+            ..Changes::default()
+        };
+        CacheEntry::NotFound.update(changes, "head")
+    }
+
+    #[test]
+    fn loading_shares_details() {
+        let cache = InMemoryCache::new();
+        let key = HocParams::new(
+            RepoId::new(Platform::GitHub, "vbrandl", "hoc").unwrap(),
+            None,
+            CountOptions::default(),
+        );
+        cache.store(key.clone(), entry()).unwrap();
+        let (
+            Some(CacheEntry::Cached {
+                details: Some(a), ..
+            }),
+            Some(CacheEntry::Cached {
+                details: Some(b), ..
+            }),
+        ) = (cache.load(&key).unwrap(), cache.load(&key).unwrap())
+        else {
+            panic!("entry is cached");
+        };
+        assert!(Arc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn details_are_not_queued_twice() {
+        let queue = Queue::new();
+        let key = HocParams::new(
+            RepoId::new(Platform::GitHub, "vbrandl", "hoc").unwrap(),
+            None,
+            CountOptions::default(),
+        );
+        assert!(queue.push(key.clone().with_details()));
+        assert!(!queue.push(key));
+    }
+
+    #[test]
+    fn details_are_stored_inline() {
+        let json = serde_json::to_value(entry()).unwrap();
+        assert_eq!(json["Cached"]["files"]["a.txt"]["additions"], 3);
+        assert!(json["Cached"]["timeline"].is_array());
+        assert!(json["Cached"].get("details").is_none());
+
+        let entry: CacheEntry = serde_json::from_value(json).unwrap();
+        assert!(matches!(
+            entry,
+            CacheEntry::Cached {
+                details: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn details_are_optional() {
+        let entry = CacheEntry::NotFound.update(
+            Changes {
+                additions: 3,
+                ..Changes::default()
+            },
+            "head",
+        );
+        let json = serde_json::to_value(&entry).unwrap();
+        assert!(json["Cached"].get("timeline").is_none());
+        assert!(json["Cached"].get("files").is_none());
+
+        let entry: CacheEntry = serde_json::from_value(json).unwrap();
+        assert!(matches!(
+            entry,
+            CacheEntry::Cached {
+                additions: 3,
+                details: None,
+                ..
+            }
+        ));
     }
 }
@@ -1,10 +1,29 @@
-use std::path::PathBuf;
+use crate::{
+    diff::DiffSettings,
+    error::{Error, Result as HocResult},
+    platform::{Forge, Platform},
+    repo_id::RepoId,
+};
+
+use std::{fmt, path::PathBuf, sync::Arc};
 
 use anyhow::Result;
 use config::{Config, ConfigError, Environment, File};
+use reqwest::Url;
 use serde::Deserialize;
 use tokio::net::TcpListener;
 
+/// Route segments that cannot be used as the name of a forge.
+const RESERVED_NAMES: &[&str] = &[
+    "git",
+    "local",
+    "static",
+    "generate",
+    "view",
+    "health",
+    "favicon.ico",
+];
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
     /// Path to store cloned repositories
@@ -19,6 +38,68 @@ pub struct Settings {
     pub base_url: String,
     /// Number of worker threads
     pub workers: usize,
+    /// Additional, self-hosted forges
+    #[serde(default)]
+    pub platforms: Vec<Forge>,
+    /// Hosts that may be used with the `/git` endpoints
+    #[serde(default)]
+    pub git_hosts: Vec<String>,
+    /// Directories containing local repositories in `<owner>/<repo>`, served via the `local`
+    /// platform
+    #[serde(default)]
+    pub local_roots: Vec<PathBuf>,
+    /// Credentials used to access private repositories
+    #[serde(default)]
+    pub credentials: Vec<Credential>,
+    /// Default diff semantics, can be overridden per request
+    #[serde(default)]
+    pub diff: DiffSettings,
+}
+
+/// Credentials for the repositories of a platform or of a single owner on that platform.
+#[derive(Deserialize, Clone)]
+pub struct Credential {
+    /// Name or domain of the platform
+    pub platform: String,
+    /// Owner or namespace the credentials are restricted to. Applies to all owners if not set
+    pub owner: Option<String>,
+    /// Username for HTTPS and SSH authentication
+    pub username: Option<String>,
+    /// Token or password for HTTPS authentication
+    pub token: Option<String>,
+    /// Path to a private key for SSH authentication
+    pub ssh_key: Option<PathBuf>,
+    /// Passphrase of the private key
+    pub passphrase: Option<String>,
+}
+
+impl Credential {
+    fn matches(&self, platform: &Platform, owner: &str) -> bool {
+        (self.platform == platform.url_path()
+            || self.platform.eq_ignore_ascii_case(platform.domain()))
+            && self.owner.as_deref().is_none_or(|o| {
+                let o = o.to_lowercase();
+                let owner = owner.to_lowercase();
+                owner == o || owner.starts_with(&format!("{o}/"))
+            })
+    }
+}
+
+// secrets must not end up in the logs
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credential")
+            .field("platform", &self.platform)
+            .field("owner", &self.owner)
+            .field("username", &self.username)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("ssh_key", &self.ssh_key)
+            .field(
+                "passphrase",
+                &self.passphrase.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
 }
 
 impl Settings {
@@ -28,8 +109,9 @@ impl Settings {
     ///
     /// * File cannot be read or parsed
     /// * Environment variables cannot be parsed
+    /// * A configured forge is invalid, see [`Settings::validate`]
     pub fn load() -> Result<Self, ConfigError> {
-        Config::builder()
+        let settings: Self = Config::builder()
             .add_source(File::with_name("hoc.toml").required(false))
             .add_source(Environment::with_prefix("hoc"))
             .set_default("repodir", "./repos")?
@@ -38,7 +120,55 @@ impl Settings {
             .set_default("port", 8080)?
             .set_default("host", "0.0.0.0")?
             .build()?
-            .try_deserialize()
+            .try_deserialize()?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Check that the configured forges neither shadow routes nor share the repositories and
+    /// cache of another platform, which are stored by domain.
+    ///
+    /// A forge may use the name and domain of a built-in platform to override it, e.g. to fetch
+    /// via SSH.
+    ///
+    /// # Errors
+    ///
+    /// * A forge is named like a route, e.g. `git` or `static`
+    /// * A forge has the name or domain of another forge
+    /// * A forge has the name of a built-in platform but another domain, or vice versa
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |forge: &Forge, reason: &str| {
+            Err(ConfigError::Message(format!(
+                "invalid platform `{}` ({}): {reason}",
+                forge.name, forge.domain
+            )))
+        };
+        for (idx, forge) in self.platforms.iter().enumerate() {
+            if RESERVED_NAMES.contains(&forge.name.as_str()) {
+                return invalid(forge, "the name is reserved");
+            }
+            let others = &self.platforms[..idx];
+            if others.iter().any(|other| other.name == forge.name) {
+                return invalid(forge, "the name is already used by another platform");
+            }
+            if others
+                .iter()
+                .any(|other| other.domain.eq_ignore_ascii_case(&forge.domain))
+            {
+                return invalid(forge, "the domain is already used by another platform");
+            }
+            if let Ok(builtin) = forge.name.parse::<Platform>()
+                && !builtin.domain().eq_ignore_ascii_case(&forge.domain)
+            {
+                return invalid(forge, "the name is used by a built-in platform");
+            }
+            if let Some(builtin) = Platform::from_domain(&forge.domain)
+                && builtin.url_path() != forge.name
+            {
+                return invalid(forge, "the domain is used by a built-in platform");
+            }
+        }
+        Ok(())
     }
 
     /// Create a [`TcpListener`] for this config.
@@ -50,7 +180,277 @@ impl Settings {
         Ok(TcpListener::bind(self.listen_addr()).await?)
     }
 
+    /// Resolve the platform for a request path segment. Configured forges take precedence over the
+    /// built-in platforms.
+    pub(crate) fn platform(&self, name: &str) -> HocResult<Platform> {
+        if let Some(forge) = self.platforms.iter().find(|forge| forge.name == name) {
+            Ok(Platform::Custom(Arc::new(forge.clone())))
+        } else if name == "local" && !self.local_roots.is_empty() {
+            Ok(Platform::Local)
+        } else {
+            name.parse()
+        }
+    }
+
+    /// Path of a local repository. The first root containing `<owner>/<repo>` is used.
+    pub(crate) fn local_repo(&self, id: &RepoId) -> PathBuf {
+        let candidates = || {
+            self.local_roots
+                .iter()
+                .map(|root| root.join(id.owner()).join(id.repo()))
+        };
+        candidates()
+            .find(|path| path.exists())
+            .or_else(|| candidates().next())
+            .unwrap_or_default()
+    }
+
+    /// Find the credentials for a repository. Credentials restricted to an owner take precedence
+    /// over credentials for the whole platform.
+    pub(crate) fn credential(&self, id: &RepoId) -> Option<&Credential> {
+        self.credentials
+            .iter()
+            .filter(|c| c.matches(id.platform(), id.owner()))
+            .max_by_key(|c| c.owner.as_ref().map_or(0, String::len))
+    }
+
+    /// Resolve a git remote URL passed to the `/git` endpoints into a repository id.
+    /// Only `https` remotes on one of the configured `git_hosts` are accepted. A trailing `.git`
+    /// is stripped, so `/owner/repo` and `/owner/repo.git` are the same repository.
+    pub(crate) fn git_repo(&self, url: &str) -> HocResult<RepoId> {
+        let invalid = || Error::InvalidRepo(url.to_string());
+        let parsed = Url::parse(url).map_err(|_| invalid())?;
+        if parsed.scheme() != "https" {
+            return Err(invalid());
+        }
+        let host = parsed.host_str().ok_or_else(invalid)?.to_lowercase();
+        let host = match parsed.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host,
+        };
+        if !self.git_hosts.iter().any(|h| h.eq_ignore_ascii_case(&host)) {
+            return Err(Error::UnknownPlatform(host));
+        }
+        let segments: Vec<_> = parsed
+            .path_segments()
+            .ok_or_else(invalid)?
+            .filter(|s| !s.is_empty())
+            .collect();
+        match segments.split_last() {
+            Some((repo, owner)) if !owner.is_empty() => {
+                let repo = repo.strip_suffix(".git").unwrap_or(repo);
+                RepoId::new(Platform::Git(host.into()), owner.join("/"), repo)
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Repository of a submodule of `parent` with the `url` from `.gitmodules`.
+    ///
+    /// Relative URLs like `../lib` are resolved against the parent, so they work on every
+    /// platform, including local repositories. Absolute URLs must point to a built-in platform, a
+    /// configured forge or one of the `git_hosts`.
+    pub(crate) fn submodule_repo(&self, parent: &RepoId, url: &str) -> HocResult<RepoId> {
+        let invalid = || Error::InvalidRepo(url.to_string());
+        let trimmed = url.trim_end_matches('/');
+        let trimmed = trimmed.strip_suffix(".git").unwrap_or(trimmed);
+        let (platform, path) = if trimmed.starts_with("./") || trimmed.starts_with("../") {
+            let mut segments: Vec<_> = parent
+                .owner()
+                .split('/')
+                .chain([parent.repo()])
+                .map(str::to_string)
+                .collect();
+            for segment in trimmed.split('/') {
+                match segment {
+                    "" | "." => {}
+                    ".." => {
+                        segments.pop().ok_or_else(invalid)?;
+                    }
+                    segment => segments.push(segment.to_string()),
+                }
+            }
+            (parent.platform().clone(), segments.join("/"))
+        } else {
+            // scp-like syntax, e.g. `git@github.com:owner/repo`
+            let parsed = match trimmed.split_once("://") {
+                Some(_) => Url::parse(trimmed),
+                None => {
+                    let (host, path) = trimmed.split_once(':').ok_or_else(invalid)?;
+                    Url::parse(&format!("ssh://{host}/{path}"))
+                }
+            }
+            .map_err(|_| invalid())?;
+            let host = parsed.host_str().ok_or_else(invalid)?;
+            let platform = if let Some(platform) = Platform::from_domain(host) {
+                platform
+            } else if let Some(forge) = self
+                .platforms
+                .iter()
+                .find(|forge| forge.domain.eq_ignore_ascii_case(host))
+            {
+                Platform::Custom(Arc::new(forge.clone()))
+            } else {
+                return self.git_repo(trimmed);
+            };
+            (platform, parsed.path().to_string())
+        };
+        let segments: Vec<_> = path.split('/').filter(|s| !s.is_empty()).collect();
+        match segments.split_last() {
+            Some((repo, owner)) if !owner.is_empty() => {
+                RepoId::new(platform, owner.join("/"), *repo)
+            }
+            _ => Err(invalid()),
+        }
+    }
+
     fn listen_addr(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Credential, Settings};
+    use crate::{platform::Platform, repo_id::RepoId};
+
+    use std::path::Path;
+
+    use serde_json::{Value, json};
+
+    fn settings(platforms: Value) -> Settings {
+        serde_json::from_value(json!({
+            "repodir": "./repos",
+            "cachedir": "./cache",
+            "port": 8080,
+            "host": "0.0.0.0",
+            "base_url": "http://localhost",
+            "workers": 1,
+            "platforms": platforms,
+        }))
+        .unwrap()
+    }
+
+    fn credential(owner: Option<&str>) -> Credential {
+        Credential {
+            platform: "gitlab".to_string(),
+            owner: owner.map(str::to_string),
+            username: None,
+            token: Some("secret".to_string()),
+            ssh_key: None,
+            passphrase: None,
+        }
+    }
+
+    #[test]
+    fn credential_for_platform() {
+        let credential = credential(None);
+        assert!(credential.matches(&Platform::Gitlab, "group"));
+        assert!(!credential.matches(&Platform::GitHub, "group"));
+    }
+
+    #[test]
+    fn credential_for_namespace() {
+        let credential = credential(Some("Group"));
+        assert!(credential.matches(&Platform::Gitlab, "group"));
+        assert!(credential.matches(&Platform::Gitlab, "group/subgroup"));
+        assert!(!credential.matches(&Platform::Gitlab, "groupie"));
+    }
+
+    #[test]
+    fn credential_debug_is_redacted() {
+        assert!(!format!("{:?}", credential(None)).contains("secret"));
+    }
+
+    #[test]
+    fn submodule_urls() {
+        let settings = settings(json!([{ "name": "example", "domain": "git.example.com" }]));
+        let parent = RepoId::new(Platform::Gitlab, "group/subgroup", "parent").unwrap();
+        let resolve = |url: &str| {
+            settings.submodule_repo(&parent, url).map(|id| {
+                (
+                    id.platform().url_path().to_string(),
+                    id.owner().to_string(),
+                    id.repo().to_string(),
+                )
+            })
+        };
+        let id = |platform: &str, owner: &str, repo: &str| {
+            (platform.to_string(), owner.to_string(), repo.to_string())
+        };
+
+        assert_eq!(
+            resolve("../lib.git").unwrap(),
+            id("gitlab", "group/subgroup", "lib")
+        );
+        assert_eq!(
+            resolve("../../other/lib").unwrap(),
+            id("gitlab", "group/other", "lib")
+        );
+        assert_eq!(
+            resolve("https://github.com/owner/lib.git").unwrap(),
+            id("github", "owner", "lib")
+        );
+        assert_eq!(
+            resolve("git@github.com:owner/lib.git").unwrap(),
+            id("github", "owner", "lib")
+        );
+        assert_eq!(
+            resolve("https://git.example.com/owner/lib/").unwrap(),
+            id("example", "owner", "lib")
+        );
+        assert!(resolve("https://unknown.example.com/owner/lib").is_err());
+        assert!(resolve("../../../../lib").is_err());
+    }
+
+    #[test]
+    fn git_suffix_is_stripped() {
+        let mut settings = settings(json!([]));
+        settings.git_hosts.push("git.example.com".to_string());
+
+        let id = settings
+            .git_repo("https://git.example.com/group/repo.git")
+            .unwrap();
+        assert_eq!(id.repo(), "repo");
+        assert_eq!(
+            id.path(Path::new("cache")),
+            Path::new("cache/git.example.com/group/repo")
+        );
+        assert_eq!(
+            id,
+            settings
+                .git_repo("https://git.example.com/group/repo")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn forge_validation() {
+        let forge = |name: &str, domain: &str| json!({ "name": name, "domain": domain });
+        let validate = |platforms: Vec<Value>| settings(Value::Array(platforms)).validate();
+
+        assert!(validate(vec![forge("example", "git.example.com")]).is_ok());
+        // overriding a built-in platform
+        assert!(validate(vec![forge("github", "github.com")]).is_ok());
+
+        assert!(validate(vec![forge("static", "git.example.com")]).is_err());
+        assert!(validate(vec![forge("git", "git.example.com")]).is_err());
+        assert!(validate(vec![forge("local", "git.example.com")]).is_err());
+        assert!(validate(vec![forge("github", "git.example.com")]).is_err());
+        assert!(validate(vec![forge("mirror", "GitHub.com")]).is_err());
+        assert!(
+            validate(vec![
+                forge("example", "git.example.com"),
+                forge("example", "other.example.com"),
+            ])
+            .is_err()
+        );
+        assert!(
+            validate(vec![
+                forge("example", "git.example.com"),
+                forge("other", "git.example.com"),
+            ])
+            .is_err()
+        );
+    }
+}
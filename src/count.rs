@@ -11,7 +11,8 @@ use tracing::{instrument, trace};
 
 /// The on disk layout for served repos is `<platform>/<user>/<repo>`
 /// so to get the amount of repos, we just have to count everything
-/// in `*/*/*` to get the count.
+/// in `*/*/*` to get the count. Nested namespaces are encoded into a single
+/// `<user>` directory, so they don't need special handling.
 ///
 /// # Errors
 ///
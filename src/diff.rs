@@ -0,0 +1,356 @@
+use crate::{
+    error::{Error, Result},
+    rename::Pairs,
+    store::CommitStore,
+};
+
+use std::collections::HashSet;
+
+use git2::{Commit, Delta, DiffOptions, FileMode, Oid, Patch, Repository, Sort, Tree};
+use serde::Deserialize;
+
+/// How changes that only touch whitespace are counted.
+#[derive(Deserialize, Clone, Copy, Default, Hash, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Whitespace {
+    /// Like `--ignore-space-change --ignore-all-space`
+    #[default]
+    Ignore,
+    Count,
+}
+
+/// Whether renamed and copied files are detected.
+#[derive(Deserialize, Clone, Copy, Default, Hash, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Renames {
+    /// Like `--find-copies-harder -M`. Renamed files are not counted, copies only count the changed
+    /// lines
+    #[default]
+    On,
+    /// Renames and copies count as deleted and added files
+    Off,
+}
+
+/// How merge commits are counted.
+#[derive(Deserialize, Clone, Copy, Default, Hash, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Merges {
+    /// Merges are not diffed, the merged commits are counted instead
+    #[default]
+    Skip,
+    /// Like `git log --first-parent`: only the first parent of merges is followed and merges are
+    /// diffed against it
+    FirstParent,
+}
+
+impl Whitespace {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Ignore => "ignore",
+            Self::Count => "count",
+        }
+    }
+}
+
+impl Renames {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::On => "on",
+            Self::Off => "off",
+        }
+    }
+}
+
+impl Merges {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::FirstParent => "first-parent",
+        }
+    }
+}
+
+/// How changed binary files are counted.
+#[derive(Deserialize, Clone, Copy, Default, Hash, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Binary {
+    /// Like `git log --numstat`, binary files are not counted
+    #[default]
+    Skip,
+    /// Each changed binary file counts as `binary_weight` lines
+    Count,
+    /// Binary files are not counted, but the number of changed binary files is reported
+    Report,
+}
+
+impl Binary {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::Count => "count",
+            Self::Report => "report",
+        }
+    }
+}
+
+/// Default number of lines a changed binary file counts as.
+pub(crate) const DEFAULT_BINARY_WEIGHT: u64 = 1;
+
+/// Largest `binary_weight` that can be requested.
+pub(crate) const MAX_BINARY_WEIGHT: u64 = 10_000;
+
+/// Semantics of the diff. The defaults match the `git log` invocation documented on
+/// [`numstat`].
+#[derive(Deserialize, Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct DiffSettings {
+    pub whitespace: Whitespace,
+    pub renames: Renames,
+    pub merges: Merges,
+    pub binary: Binary,
+    /// Number of lines a changed binary file counts as, if `binary` is [`Binary::Count`]
+    pub binary_weight: u64,
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        Self {
+            whitespace: Whitespace::default(),
+            renames: Renames::default(),
+            merges: Merges::default(),
+            binary: Binary::default(),
+            binary_weight: DEFAULT_BINARY_WEIGHT,
+        }
+    }
+}
+
+impl DiffSettings {
+    /// Suffix of the cache key. Only settings that differ from the default are added.
+    pub(crate) fn cache_key(&self) -> String {
+        let mut key = String::new();
+        if self.whitespace != Whitespace::default() {
+            key.push_str("&whitespace=");
+            key.push_str(self.whitespace.as_str());
+        }
+        if self.renames != Renames::default() {
+            key.push_str("&renames=");
+            key.push_str(self.renames.as_str());
+        }
+        if self.merges != Merges::default() {
+            key.push_str("&merges=");
+            key.push_str(self.merges.as_str());
+        }
+        if self.binary != Binary::default() {
+            key.push_str("&binary=");
+            key.push_str(self.binary.as_str());
+        }
+        if self.binary == Binary::Count && self.binary_weight != DEFAULT_BINARY_WEIGHT {
+            key.push_str(&format!("&binary_weight={}", self.binary_weight));
+        }
+        key
+    }
+
+    /// Name of the [`CommitStore`] file.
+    ///
+    /// The stats of a commit depend on the whitespace and rename handling, so each combination is
+    /// stored separately. Merges are only read from the store if they are diffed, so both merge
+    /// modes share a file. Binary files are always stored.
+    pub(crate) fn store_name(&self) -> String {
+        let mut name = "numstat".to_string();
+        if self.whitespace != Whitespace::default() {
+            name.push_str("-whitespace-");
+            name.push_str(self.whitespace.as_str());
+        }
+        if self.renames != Renames::default() {
+            name.push_str("-renames-");
+            name.push_str(self.renames.as_str());
+        }
+        name
+    }
+}
+
+/// Added and deleted lines of a single file in a commit.
+///
+/// Binary files have no lines, so a changed binary file has one addition, or one deletion if it
+/// was deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FileStat {
+    /// Path of the file after the commit, or before it, if the file was deleted
+    pub(crate) path: String,
+    pub(crate) additions: u64,
+    pub(crate) deletions: u64,
+    pub(crate) binary: bool,
+}
+
+/// In-process equivalent of the following command with the default [`DiffSettings`]
+///
+/// ```text
+/// git log --numstat --ignore-space-change --ignore-all-space --ignore-submodules \
+///     --find-copies-harder -M --diff-filter=ACDM <to> ^<hide>... -- .
+/// ```
+///
+/// `on_commit` is called once for every commit that `git log` would show, with the stats of the
+/// files changed by that commit, so the output is never buffered. Merge commits are not diffed
+/// and root commits are diffed against the empty tree. Like the pathspec `.` does, a merge that
+/// has the same tree as one of its parents is only followed into that parent.
+///
+/// `settings` change the whitespace, rename and merge handling.
+///
+/// The stored stats of the walked commits are read from `store`, only new commits are diffed and
+/// added to it.
+///
+/// The walk stops with [`Error::Cancelled`] as soon as `cancelled` returns `true`.
+///
+/// Returns the number of commits reachable from any commit in `to` but not from any commit in
+/// `hide`, like `git rev-list --count`. Commits reachable from several commits in `to` are only
+/// walked once.
+pub(crate) fn numstat(
+    repo: &Repository,
+    hide: &[Oid],
+    to: &[Oid],
+    settings: DiffSettings,
+    store: &mut CommitStore,
+    cancelled: impl Fn() -> bool,
+    mut on_commit: impl FnMut(&Commit<'_>, &[FileStat]),
+) -> Result<u64> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    if settings.merges == Merges::FirstParent {
+        revwalk.simplify_first_parent()?;
+    }
+    for oid in to {
+        revwalk.push(*oid)?;
+    }
+    for oid in hide {
+        revwalk.hide(*oid)?;
+    }
+    let range = revwalk.collect::<std::result::Result<Vec<_>, _>>()?;
+    let in_range: HashSet<_> = range.iter().copied().collect();
+
+    // `git log -- .` only follows the parent a merge is identical to, if there is one
+    let mut reachable: HashSet<_> = to.iter().copied().collect();
+    for oid in range {
+        if cancelled() {
+            return Err(Error::Cancelled);
+        }
+        if !reachable.contains(&oid) {
+            continue;
+        }
+        let commit = repo.find_commit(oid)?;
+        let parents: Vec<_> = commit.parents().collect();
+        // the parent the commit is diffed against, `None` for root commits
+        let (follow, diffed) = match (parents.as_slice(), settings.merges) {
+            ([], _) => (Vec::new(), Some(None)),
+            ([parent], Merges::Skip) | ([parent, ..], Merges::FirstParent) => {
+                (vec![parent.id()], Some(Some(parent)))
+            }
+            (parents, Merges::Skip) => (
+                parents
+                    .iter()
+                    .find(|p| p.tree_id() == commit.tree_id())
+                    .map_or_else(
+                        || parents.iter().map(Commit::id).collect(),
+                        |p| vec![p.id()],
+                    ),
+                None,
+            ),
+        };
+        reachable.extend(follow.into_iter().filter(|p| in_range.contains(p)));
+        let files = match diffed {
+            // the store may contain the diff of a merge against the first parent
+            None => Vec::new(),
+            Some(parent) => {
+                if let Some(files) = store.get(oid)? {
+                    files
+                } else {
+                    let old = parent.map(Commit::tree).transpose()?;
+                    let files = diff(repo, old.as_ref(), &commit.tree()?, settings)?;
+                    store.insert(oid, &files)?;
+                    files
+                }
+            }
+        };
+        on_commit(&commit, &files);
+    }
+    Ok(in_range.len() as u64)
+}
+
+fn diff(
+    repo: &Repository,
+    old: Option<&Tree<'_>>,
+    new: &Tree<'_>,
+    settings: DiffSettings,
+) -> Result<Vec<FileStat>> {
+    let ignore_whitespace = settings.whitespace == Whitespace::Ignore;
+    let renames = settings.renames == Renames::On;
+    let mut options = DiffOptions::new();
+    options
+        .ignore_whitespace(ignore_whitespace)
+        .ignore_whitespace_change(ignore_whitespace)
+        .ignore_submodules(true)
+        .context_lines(0);
+    let diff = repo.diff_tree_to_tree(old, Some(new), Some(&mut options))?;
+    // libgit2 estimates the similarity differently and pairs other files than git
+    let pairs = if renames {
+        Pairs::find(repo, old, &diff)?
+    } else {
+        Pairs::default()
+    };
+
+    let mut files = Vec::new();
+    for (idx, delta) in diff.deltas().enumerate() {
+        let (Some(path), Some(path_bytes)) = (
+            delta.new_file().path().or_else(|| delta.old_file().path()),
+            delta
+                .new_file()
+                .path_bytes()
+                .or_else(|| delta.old_file().path_bytes()),
+        ) else {
+            continue;
+        };
+        match delta.status() {
+            Delta::Added if pairs.is_renamed(path_bytes) => continue,
+            Delta::Deleted if pairs.is_moved(path_bytes) => continue,
+            Delta::Added | Delta::Deleted | Delta::Modified => {}
+            _ => continue,
+        }
+        // `ignore_submodules` does not apply to tree diffs
+        if delta.new_file().mode() == FileMode::Commit
+            || delta.old_file().mode() == FileMode::Commit
+        {
+            continue;
+        }
+        let patch = match pairs.source(path_bytes) {
+            Some(source) => Some(Patch::from_blobs(
+                &repo.find_blob(source)?,
+                None,
+                &repo.find_blob(delta.new_file().id())?,
+                None,
+                Some(&mut options),
+            )?),
+            None => Patch::from_diff(&diff, idx)?,
+        };
+        let Some(patch) = patch else {
+            continue;
+        };
+        // binary files have no line stats, just like `git log --numstat`
+        if patch.delta().flags().is_binary() {
+            let deleted = delta.status() == Delta::Deleted;
+            files.push(FileStat {
+                path: path.to_string_lossy().into_owned(),
+                additions: u64::from(!deleted),
+                deletions: u64::from(deleted),
+                binary: true,
+            });
+            continue;
+        }
+        let (_, additions, deletions) = patch.line_stats()?;
+        files.push(FileStat {
+            path: path.to_string_lossy().into_owned(),
+            additions: additions as u64,
+            deletions: deletions as u64,
+            binary: false,
+        });
+    }
+    Ok(files)
+}
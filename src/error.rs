@@ -23,6 +23,14 @@ pub enum Error {
     BranchNotFound,
     #[error("UnknownPlatform({0})")]
     UnknownPlatform(String),
+    #[error("InvalidRepo({0})")]
+    InvalidRepo(String),
+    #[error("InvalidQuery({0})")]
+    InvalidQuery(String),
+    #[error("InvalidRevision({0})")]
+    InvalidRevision(String),
+    #[error("Cancelled")]
+    Cancelled,
     #[error("Join({0})")]
     Join(#[from] JoinError),
     #[error(transparent)]
@@ -0,0 +1,243 @@
+use crate::{cache::TimelineEntry, window::Window};
+
+use std::{
+    collections::BTreeMap,
+    ops::Bound::{Excluded, Unbounded},
+};
+
+use jiff::{Timestamp, ToSpan, civil::Date, tz::TimeZone};
+use serde::{Deserialize, Serialize};
+
+/// Gaps are only filled, if the series has at most that many buckets. Otherwise, e.g. because of
+/// a commit with a bogus date, only the buckets with commits are returned.
+const MAX_FILLED_BUCKETS: i64 = 10_000;
+
+/// Length of the periods the history is grouped by.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Bucket {
+    Day,
+    /// ISO week, starting on Monday
+    #[default]
+    Week,
+}
+
+impl Bucket {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+        }
+    }
+
+    /// First day of the bucket containing `date`.
+    fn start(self, date: Date) -> Date {
+        match self {
+            Self::Day => date,
+            Self::Week => date
+                .checked_sub(i64::from(date.weekday().to_monday_zero_offset()).days())
+                .unwrap_or(date),
+        }
+    }
+
+    fn next(self, date: Date) -> Option<Date> {
+        match self {
+            Self::Day => date.checked_add(1.day()).ok(),
+            Self::Week => date.checked_add(1.week()).ok(),
+        }
+    }
+
+    /// Number of buckets from the bucket starting at `first` to the one starting at `last`.
+    fn count(self, first: Date, last: Date) -> i64 {
+        let days = first
+            .until(last)
+            .map_or(i64::MAX, |span| i64::from(span.get_days()));
+        match self {
+            Self::Day => days,
+            Self::Week => days / 7,
+        }
+        .saturating_add(1)
+    }
+}
+
+/// HoC and commits of one bucket.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub(crate) struct Point {
+    /// First day of the bucket in UTC, e.g. `2024-01-01`
+    pub(crate) date: String,
+    /// Sum of `additions` and `deletions`
+    pub(crate) count: u64,
+    pub(crate) additions: u64,
+    pub(crate) deletions: u64,
+    pub(crate) commits: u64,
+    /// HoC up to and including this bucket
+    pub(crate) cumulative_count: u64,
+    /// Commits up to and including this bucket
+    pub(crate) cumulative_commits: u64,
+}
+
+/// Group the commits of `timeline` inside `window` into buckets.
+///
+/// Buckets without commits between the first and the last commit are included, so the series can
+/// be plotted as is, unless there would be more than [`MAX_FILLED_BUCKETS`] buckets.
+pub(crate) fn series(timeline: &[TimelineEntry], window: &Window, bucket: Bucket) -> Vec<Point> {
+    let mut buckets: BTreeMap<Date, (u64, u64, u64)> = BTreeMap::new();
+    for entry in timeline {
+        let Ok(time) = Timestamp::from_second(entry.time) else {
+            continue;
+        };
+        if !window.contains(time) {
+            continue;
+        }
+        let date = bucket.start(time.to_zoned(TimeZone::UTC).date());
+        let (additions, deletions, commits) = buckets.entry(date).or_default();
+        *additions = additions.saturating_add(entry.additions);
+        *deletions = deletions.saturating_add(entry.deletions);
+        *commits += 1;
+    }
+
+    let (Some(first), Some(last)) = (
+        buckets.first_key_value().map(|(date, _)| *date),
+        buckets.last_key_value().map(|(date, _)| *date),
+    ) else {
+        return Vec::new();
+    };
+    let fill = bucket.count(first, last) <= MAX_FILLED_BUCKETS;
+    let next = |date: &Date| {
+        if fill {
+            bucket.next(*date)
+        } else {
+            buckets
+                .range((Excluded(*date), Unbounded))
+                .next()
+                .map(|(date, _)| *date)
+        }
+    };
+
+    let mut points = Vec::new();
+    let (mut cumulative_count, mut cumulative_commits) = (0, 0);
+    for current in std::iter::successors(Some(first), next).take_while(|date| *date <= last) {
+        let (additions, deletions, commits) = buckets.get(&current).copied().unwrap_or_default();
+        let count = additions.saturating_add(deletions);
+        cumulative_count = count.saturating_add(cumulative_count);
+        cumulative_commits += commits;
+        points.push(Point {
+            date: current.to_string(),
+            count,
+            additions,
+            deletions,
+            commits,
+            cumulative_count,
+            cumulative_commits,
+        });
+    }
+    points
+}
+
+/// Render the series as CSV with a header line.
+pub(crate) fn csv(points: &[Point]) -> String {
+    let mut csv =
+        "date,count,additions,deletions,commits,cumulative_count,cumulative_commits\n".to_string();
+    for point in points {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            point.date,
+            point.count,
+            point.additions,
+            point.deletions,
+            point.commits,
+            point.cumulative_count,
+            point.cumulative_commits
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bucket, csv, series};
+    use crate::{cache::TimelineEntry, window::Window};
+
+    use jiff::Timestamp;
+
+    fn entry(time: &str, additions: u64, deletions: u64) -> TimelineEntry {
+        TimelineEntry {
+            time: time.parse::<Timestamp>().unwrap().as_second(),
+            additions,
+            deletions,
+            binary_changes: 0,
+        }
+    }
+
+    #[test]
+    fn daily_buckets_fill_gaps() {
+        let timeline = [
+            entry("2024-01-03T10:00:00Z", 1, 1),
+            entry("2024-01-01T23:59:59Z", 2, 0),
+            entry("2024-01-01T00:00:00Z", 3, 0),
+        ];
+        let points: Vec<_> = series(&timeline, &Window::default(), Bucket::Day)
+            .into_iter()
+            .map(|p| {
+                (
+                    p.date,
+                    p.count,
+                    p.commits,
+                    p.cumulative_count,
+                    p.cumulative_commits,
+                )
+            })
+            .collect();
+        assert_eq!(
+            points,
+            vec![
+                ("2024-01-01".to_string(), 5, 2, 5, 2),
+                ("2024-01-02".to_string(), 0, 0, 5, 2),
+                ("2024-01-03".to_string(), 2, 1, 7, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn weekly_buckets_start_on_monday() {
+        // 2024-01-07 is a Sunday, 2024-01-08 a Monday
+        let timeline = [
+            entry("2024-01-07T12:00:00Z", 1, 0),
+            entry("2024-01-01T12:00:00Z", 1, 0),
+            entry("2024-01-08T12:00:00Z", 4, 2),
+        ];
+        let points = series(&timeline, &Window::default(), Bucket::Week);
+        assert_eq!(
+            csv(&points),
+            "date,count,additions,deletions,commits,cumulative_count,cumulative_commits\n\
+             2024-01-01,2,2,0,2,2,2\n\
+             2024-01-08,6,4,2,1,8,3\n"
+        );
+    }
+
+    #[test]
+    fn gaps_are_not_filled_for_bogus_dates() {
+        let timeline = [
+            entry("1970-01-01T00:00:00Z", 1, 0),
+            entry("2024-01-01T00:00:00Z", 2, 0),
+            entry("9999-01-01T00:00:00Z", 3, 0),
+        ];
+        let points: Vec<_> = series(&timeline, &Window::default(), Bucket::Day)
+            .into_iter()
+            .map(|p| (p.date, p.count, p.cumulative_count))
+            .collect();
+        assert_eq!(
+            points,
+            vec![
+                ("1970-01-01".to_string(), 1, 1),
+                ("2024-01-01".to_string(), 2, 3),
+                ("9999-01-01".to_string(), 3, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_timeline() {
+        assert!(series(&[], &Window::default(), Bucket::Week).is_empty());
+    }
+}
@@ -1,34 +1,283 @@
 use crate::{
-    cache::{Cache, CacheEntry, Excludes, HocParams},
+    attributes::{self, Attributes},
+    cache::{
+        Author, Cache, CacheEntry, Changes, CountOptions, Details, HocParams, Lines,
+        SubmoduleStats, TimelineEntry,
+    },
+    config::{Credential, Settings},
+    diff::{Binary, DiffSettings, FileStat, numstat},
     error::{Error, Result},
     http::AppState,
+    platform::{Platform, Ssh},
+    repo_id::RepoId,
+    ssh::is_known_host,
+    store::CommitStore,
+    submodule::{self, Submodule},
 };
 
-use std::{path::Path, process::Command, sync::atomic::Ordering};
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    fs::{create_dir_all, remove_dir_all, rename},
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::Ordering},
+};
 
-use git2::{BranchType, ErrorCode, Repository, build::RepoBuilder};
+use git2::{
+    AutotagOption, BranchType, CertificateCheckStatus, Commit, Cred, CredentialType, ErrorClass,
+    ErrorCode, FetchOptions, FetchPrune, Mailmap, ObjectType, Oid, Reference, RemoteCallbacks,
+    Repository, Signature, build::RepoBuilder,
+};
 use gix_glob::{Pattern, pattern::Case, wildmatch::Mode};
+use reqwest::Url;
 use tracing::{debug, info, instrument, trace, warn};
 
-#[instrument("fetch", skip(path), fields(path = ?path.as_ref().display()))]
-fn fetch(path: impl AsRef<Path>, branch: Option<&str>) -> Result<()> {
+/// Everything needed to authenticate against a remote and to verify its identity.
+#[derive(Clone, Default, Debug)]
+struct RemoteAuth {
+    credential: Option<Credential>,
+    ssh: Option<Ssh>,
+}
+
+impl RemoteAuth {
+    fn new(params: &HocParams, settings: &Settings) -> Self {
+        Self {
+            credential: settings.credential(&params.id).cloned(),
+            ssh: params.id.platform().ssh().cloned(),
+        }
+    }
+
+    fn ssh_key(&self) -> Option<&Path> {
+        self.credential
+            .as_ref()
+            .and_then(|c| c.ssh_key.as_deref())
+            .or_else(|| self.ssh.as_ref().and_then(|s| s.key.as_deref()))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.credential.is_some() || self.ssh_key().is_some()
+    }
+
+    /// Fetch options using the configured credentials and `known_hosts`, if any.
+    fn fetch_options(&self) -> FetchOptions<'_> {
+        let mut options = FetchOptions::new();
+        options.remote_callbacks(self.remote_callbacks());
+        options
+    }
+
+    fn remote_callbacks(&self) -> RemoteCallbacks<'_> {
+        let mut callbacks = RemoteCallbacks::new();
+        if self.is_configured() {
+            // libgit2 calls the callback again, if authentication fails, so we have to stop at
+            // some point
+            let mut attempts = 0;
+            callbacks.credentials(move |_url, username_from_url, allowed| {
+                attempts += 1;
+                if attempts > 3 {
+                    return Err(git2::Error::new(
+                        ErrorCode::Auth,
+                        ErrorClass::Callback,
+                        "authentication failed",
+                    ));
+                }
+                let credential = self.credential.as_ref();
+                let username = credential
+                    .and_then(|c| c.username.as_deref())
+                    .or(username_from_url)
+                    .unwrap_or("git");
+                if allowed.contains(CredentialType::USERNAME) {
+                    Cred::username(username)
+                } else if allowed.contains(CredentialType::SSH_KEY)
+                    && let Some(key) = self.ssh_key()
+                {
+                    let passphrase = credential.and_then(|c| c.passphrase.as_deref());
+                    Cred::ssh_key(username, None, key, passphrase)
+                } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT)
+                    && let Some(token) = credential.and_then(|c| c.token.as_deref())
+                {
+                    Cred::userpass_plaintext(username, token)
+                } else {
+                    Err(git2::Error::new(
+                        ErrorCode::Auth,
+                        ErrorClass::Callback,
+                        "no matching credentials configured",
+                    ))
+                }
+            });
+        }
+        if let Some(ssh) = &self.ssh
+            && let Some(known_hosts) = &ssh.known_hosts
+        {
+            callbacks.certificate_check(move |cert, host| {
+                let Some(hostkey) = cert.as_hostkey() else {
+                    return Ok(CertificateCheckStatus::CertificatePassthrough);
+                };
+                match (hostkey.hostkey(), hostkey.hostkey_type()) {
+                    (Some(key), Some(key_type))
+                        if is_known_host(known_hosts, host, ssh.port, key_type.name(), key) =>
+                    {
+                        Ok(CertificateCheckStatus::CertificateOk)
+                    }
+                    _ => {
+                        warn!(host, "host key verification failed");
+                        Err(git2::Error::new(
+                            ErrorCode::Certificate,
+                            ErrorClass::Ssh,
+                            "host key verification failed",
+                        ))
+                    }
+                }
+            });
+        }
+        callbacks
+    }
+}
+
+/// Fetch all branches into local branches of the bare repository.
+const BRANCHES_REFSPEC: &str = "refs/heads/*:refs/heads/*";
+
+/// Fetch all tags, also those that are not reachable from a branch.
+const TAGS_REFSPEC: &str = "+refs/tags/*:refs/tags/*";
+
+/// Fetch from `origin` and point `HEAD` to the current default branch of the remote.
+///
+/// Returns `true`, if the default branch changed.
+#[instrument("fetch", skip(path, auth), fields(path = ?path.as_ref().display()))]
+fn fetch(path: impl AsRef<Path>, branch: Option<&str>, auth: &RemoteAuth) -> Result<bool> {
     info!("fetching");
     let repo = Repository::open_bare(path)?;
     let mut origin = repo.find_remote("origin")?;
-    origin.fetch(&[branch.unwrap_or("refs/heads/*:refs/heads/*")], None, None)?;
-    Ok(())
+
+    let mut options = auth.fetch_options();
+    if branch.is_none() {
+        // deleted branches must not be counted for `branch=*`
+        options.prune(FetchPrune::On);
+    }
+    origin.fetch(
+        &[branch.unwrap_or(BRANCHES_REFSPEC), TAGS_REFSPEC],
+        Some(&mut options),
+        None,
+    )?;
+    // still known after the fetch disconnected
+    let default_branch = origin
+        .default_branch()
+        .ok()
+        .and_then(|b| b.as_str().ok().map(str::to_string));
+
+    let current = repo
+        .find_reference("HEAD")?
+        .symbolic_target()?
+        .map(str::to_string);
+    match default_branch {
+        Some(default_branch) if current.as_deref() != Some(default_branch.as_str()) => {
+            info!(?current, new = default_branch, "default branch changed");
+            repo.set_head(&default_branch)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
 }
 
-#[instrument("clone", skip(path), fields(path = ?path.as_ref().display(), origin))]
-fn clone(path: impl AsRef<Path>, origin: &str) -> Result<Option<Repository>> {
+#[instrument("clone", skip(path, auth), fields(path = ?path.as_ref().display(), origin))]
+fn clone(path: impl AsRef<Path>, origin: &str, auth: &RemoteAuth) -> Result<Option<Repository>> {
     info!("cloning");
-    Ok(
-        match RepoBuilder::new().bare(true).clone(origin, path.as_ref()) {
-            Ok(repo) => Ok(Some(repo)),
-            Err(e) if e.code() == ErrorCode::Auth => Ok(None),
-            Err(e) => Err(e),
-        }?,
-    )
+    let mut options = auth.fetch_options();
+    // tags are needed for the `from` and `to` parameters
+    options.download_tags(AutotagOption::All);
+    Ok(match RepoBuilder::new()
+        .bare(true)
+        .fetch_options(options)
+        // create all branches like `fetch` does, not only the default branch
+        .remote_create(|repo, name, url| repo.remote_with_fetch(name, url, BRANCHES_REFSPEC))
+        .clone(origin, path.as_ref())
+    {
+        Ok(repo) => Ok(Some(repo)),
+        Err(e) if e.code() == ErrorCode::Auth => Ok(None),
+        Err(e) => Err(e),
+    }?)
+}
+
+/// Full or abbreviated commit hash.
+fn is_commit_hash(revision: &str) -> bool {
+    (4..=40).contains(&revision.len()) && revision.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// References `revision` may name: itself, if it is a full branch or tag name, else a branch or a
+/// tag by that name.
+fn reference_names(revision: &str) -> Vec<String> {
+    if revision.starts_with("refs/heads/") || revision.starts_with("refs/tags/") {
+        vec![revision.to_string()]
+    } else {
+        vec![
+            format!("refs/heads/{revision}"),
+            format!("refs/tags/{revision}"),
+        ]
+    }
+}
+
+/// Whether `revision` is a commit hash or a valid branch or tag name.
+///
+/// Other revision syntax, like `:/<regex>` that searches all commit messages or reflog entries, is
+/// not accepted from requests.
+pub(crate) fn is_valid_revision(revision: &str) -> bool {
+    is_commit_hash(revision)
+        || reference_names(revision)
+            .iter()
+            .all(|name| Reference::is_valid_name(name))
+}
+
+/// Resolve a branch, tag or commit hash to a commit.
+fn resolve_revision(repo: &Repository, revision: &str) -> Result<Oid> {
+    if !is_valid_revision(revision) {
+        return Err(Error::InvalidRevision(revision.to_string()));
+    }
+    let object = if let Some(reference) = reference_names(revision)
+        .iter()
+        .find_map(|name| repo.find_reference(name).ok())
+    {
+        reference.peel(ObjectType::Any)
+    } else if is_commit_hash(revision) {
+        repo.revparse_single(revision)
+    } else {
+        return Err(Error::BranchNotFound);
+    };
+    object
+        .and_then(|object| object.peel_to_commit())
+        .map(|commit| commit.id())
+        .map_err(|_| Error::BranchNotFound)
+}
+
+/// Resolve a local branch to the commit it points to.
+fn find_branch(repo: &Repository, branch: &str) -> Result<Oid> {
+    repo.find_branch(branch, BranchType::Local)
+        .map_err(|_| Error::BranchNotFound)?
+        .into_reference()
+        .target()
+        .ok_or(Error::BranchNotFound)
+}
+
+/// Commits the local branches point to, sorted and without duplicates.
+fn branch_heads(repo: &Repository) -> Result<Vec<Oid>> {
+    let mut heads = Vec::new();
+    for branch in repo.branches(Some(BranchType::Local))? {
+        if let Some(head) = branch?.0.get().target() {
+            heads.push(head);
+        }
+    }
+    heads.sort();
+    heads.dedup();
+    Ok(heads)
+}
+
+/// Whether every commit in `old` is still reachable from a branch head in `heads`. Otherwise a
+/// branch was deleted or rewritten and its commits must no longer be counted.
+fn contains_branches(repo: &Repository, heads: &[Oid], old: &[String]) -> bool {
+    old.iter().all(|old| {
+        Oid::from_str(old).is_ok_and(|old| {
+            heads
+                .iter()
+                .any(|&head| head == old || repo.graph_descendant_of(head, old).unwrap_or(false))
+        })
+    })
 }
 
 fn find_default_branch(repo: &Repository) -> Result<String> {
@@ -37,146 +286,700 @@ fn find_default_branch(repo: &Repository) -> Result<String> {
     Ok(head.strip_prefix("refs/heads/").unwrap_or(head).to_string())
 }
 
+/// Owner and name of the repository, `target` points to, if a request for `owner/repo` at
+/// `origin` was redirected to another repository on the same host.
+fn redirect_target(
+    origin: &Url,
+    target: &Url,
+    owner: &str,
+    repo: &str,
+) -> Option<(String, String)> {
+    if origin.host_str() != target.host_str() || origin.port() != target.port() {
+        return None;
+    }
+    // keep any prefix of the clone URL template, e.g. `/git/{owner}/{repo}`
+    let prefix = {
+        let path = origin.path().to_ascii_lowercase();
+        let slug = format!("{owner}/{repo}").to_ascii_lowercase();
+        &origin.path()[..path.find(&slug)?]
+    };
+    let path = target
+        .path()
+        .strip_suffix("/info/refs")?
+        .strip_prefix(prefix)?;
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let (new_owner, new_repo) = path.rsplit_once('/')?;
+    if new_owner.is_empty()
+        || new_repo.is_empty()
+        || new_owner.split('/').any(str::is_empty)
+        || (new_owner.eq_ignore_ascii_case(owner) && new_repo.eq_ignore_ascii_case(repo))
+    {
+        None
+    } else {
+        Some((new_owner.to_string(), new_repo.to_string()))
+    }
+}
+
+/// Ask the remote, where the repository lives, using the same request a clone or fetch over HTTP
+/// starts with.
+async fn find_redirect(params: &HocParams, state: &AppState) -> Option<(String, String)> {
+    if *params.id.platform() == Platform::Local {
+        return None;
+    }
+    let url = params.url();
+    let origin = Url::parse(&url).ok()?;
+    if !matches!(origin.scheme(), "http" | "https") {
+        return None;
+    }
+
+    let mut request = state.client.get(format!(
+        "{}/info/refs?service=git-upload-pack",
+        url.trim_end_matches('/')
+    ));
+    if let Some(credential) = state.settings.credential(&params.id)
+        && let Some(token) = &credential.token
+    {
+        request = request.basic_auth(credential.username.as_deref().unwrap_or("git"), Some(token));
+    }
+    match request.send().await {
+        Ok(response) => {
+            redirect_target(&origin, response.url(), params.id.owner(), params.id.repo())
+        }
+        Err(err) => {
+            warn!(%err, "cannot check for redirects");
+            None
+        }
+    }
+}
+
+/// Detect renamed or transferred repositories and move the bare repository and cache over to the
+/// canonical location.
+///
+/// Returns the canonical location, if the repository moved.
+#[instrument(skip(state))]
+async fn follow_redirect(params: &HocParams, state: &AppState) -> Result<Option<HocParams>> {
+    let Some((owner, repo)) = find_redirect(params, state).await else {
+        // an expired alias is no longer valid, e.g. a new repository was created at the location
+        state.cache.remove_alias(&params.id)?;
+        return Ok(None);
+    };
+    info!(owner, repo, "repository moved");
+    let canonical = HocParams {
+        id: params.id.renamed(owner, repo)?,
+        ..params.clone()
+    };
+
+    let old_path = params.repo(&state.settings);
+    let new_path = canonical.repo(&state.settings);
+    if old_path.exists() {
+        if new_path.exists() {
+            remove_dir_all(&old_path)?;
+            state.repo_count.fetch_sub(1, Ordering::Relaxed);
+        } else {
+            create_dir_all(new_path.parent().ok_or(Error::Internal)?)?;
+            rename(&old_path, &new_path)?;
+            Repository::open_bare(&new_path)?.remote_set_url("origin", &canonical.url())?;
+        }
+    }
+    state.cache.clear(&params.id)?;
+    state.cache.store_alias(&params.id, &canonical.id)?;
+    Ok(Some(canonical))
+}
+
 #[instrument(skip(state))]
 async fn open_repo(params: &HocParams, state: &AppState) -> Result<Option<Repository>> {
     let repo_path = params.repo(&state.settings);
-    let repo = if repo_path.exists() {
+    let auth = RemoteAuth::new(params, &state.settings);
+    let repo = if *params.id.platform() == Platform::Local {
+        trace!("using local repo");
+        match Repository::open(&repo_path) {
+            Ok(repo) => Some(repo),
+            Err(e) if e.code() == ErrorCode::NotFound => {
+                warn!("repository does not exist");
+                state.cache.store(params.clone(), CacheEntry::NotFound)?;
+                None
+            }
+            Err(e) => Err(e)?,
+        }
+    } else if repo_path.exists() {
         trace!("using existing repo");
         let repo = Repository::open_bare(&repo_path)?;
-        {
+        let has_credential = auth.is_configured();
+        let fetched = {
             let repo_path = repo_path.clone();
-            let branch = params.branch.clone();
+            // all branches are fetched by default
+            let branch = params.branch.clone().filter(|_| !params.all_branches());
             //
             // TODO: this will not abort nicely and must wait for the current fetch to complete
-            tokio::task::spawn_blocking(move || fetch(&repo_path, branch.as_deref()))
+            tokio::task::spawn_blocking(move || fetch(&repo_path, branch.as_deref(), &auth))
+        }
+        .await?;
+        match fetched {
+            Ok(default_branch_changed) => {
+                if default_branch_changed {
+                    state.cache.clear_default_branch(&params.id)?;
+                }
+                Some(repo)
+            }
+            // e.g. the credentials were revoked or the repository was deleted
+            Err(Error::Git(e)) if e.code() == ErrorCode::Auth => {
+                store_rejected(params, state, has_credential)?;
+                None
+            }
+            Err(e) => Err(e)?,
         }
-        .await??;
-        Some(repo)
     } else {
         let url = params.url();
         info!("cloning for the first time");
+        let has_credential = auth.is_configured();
         if let Some(repo) = {
             let repo_path = repo_path.clone();
-            tokio::task::spawn_blocking(move || clone(&repo_path, &url))
+            tokio::task::spawn_blocking(move || clone(&repo_path, &url, &auth))
         }
         .await??
         {
             state.repo_count.fetch_add(1, Ordering::Relaxed);
             Some(repo)
         } else {
-            warn!("repository does not exist");
-            state.cache.store(params.clone(), CacheEntry::NotFound)?;
+            store_rejected(params, state, has_credential)?;
             None
         }
     };
     Ok(repo)
 }
 
+/// Cache that the remote rejected a clone or fetch with an authentication error.
+fn store_rejected(params: &HocParams, state: &AppState, has_credential: bool) -> Result<()> {
+    if has_credential {
+        warn!("not authorized to access repository");
+        state.cache.store(params.clone(), CacheEntry::Unauthorized)
+    } else {
+        // without credentials, forges respond to requests for missing repositories with an
+        // authentication request
+        warn!("repository does not exist");
+        state.cache.store(params.clone(), CacheEntry::NotFound)
+    }
+}
+
 #[instrument(skip(state))]
-pub(crate) async fn hoc(params: &HocParams, state: &AppState) -> Result<()> {
-    let Some(repo) = open_repo(params, state).await? else {
+pub(crate) async fn hoc(params: &HocParams, state: &Arc<AppState>) -> Result<()> {
+    let params = &HocParams {
+        id: state.cache.resolve_alias(params.id.clone())?,
+        ..params.clone()
+    };
+    // the remote is only asked for redirects before the first clone, so a moved repository is not
+    // cloned at its old location, and after fetching failed. Otherwise the fetch follows them.
+    let cloned = params.repo(&state.settings).exists();
+    let moved = if cloned {
+        None
+    } else {
+        follow_redirect(params, state).await?
+    };
+    let params = moved.as_ref().unwrap_or(params);
+    let (params, repo) = match open_repo(params, state).await {
+        Err(err @ Error::Git(_)) if cloned => match follow_redirect(params, state).await? {
+            Some(canonical) => {
+                let repo = open_repo(&canonical, state).await?;
+                (canonical, repo)
+            }
+            None => return Err(err),
+        },
+        repo => (params.clone(), repo?),
+    };
+    let params = &params;
+    let Some(repo) = repo else {
         return Ok(());
     };
 
-    let branch = if let Some(ref branch) = params.branch {
-        branch.clone()
+    let (head, branches) = if let Some(to) = &params.options.to {
+        (resolve_revision(&repo, to)?, Vec::new())
+    } else if params.all_branches() {
+        let branches = branch_heads(&repo)?;
+        // the default branch is reported as head, e.g. for the commit link on the overview page
+        let head = find_default_branch(&repo)
+            .and_then(|branch| find_branch(&repo, &branch))
+            .ok()
+            .or_else(|| branches.first().copied())
+            .ok_or(Error::BranchNotFound)?;
+        (head, branches)
     } else {
-        find_default_branch(&repo)?
+        let branch = if let Some(ref branch) = params.branch {
+            branch.clone()
+        } else {
+            find_default_branch(&repo)?
+        };
+
+        (find_branch(&repo, &branch)?, Vec::new())
     };
+    let branch_names: Vec<_> = branches.iter().map(Oid::to_string).collect();
+    let base = params
+        .options
+        .from
+        .as_deref()
+        .map(|from| resolve_revision(&repo, from))
+        .transpose()?;
 
-    let head = repo
-        .find_branch(&branch, BranchType::Local)
-        .map_err(|_| Error::BranchNotFound)?
-        .into_reference();
-    let head = format!("{}", head.target().ok_or(Error::BranchNotFound)?);
-
-    let mut arg_commit_count = vec!["rev-list".to_string(), "--count".to_string()];
-    let mut arg = vec![
-        "log".to_string(),
-        "--pretty=tformat:".to_string(),
-        "--numstat".to_string(),
-        "--ignore-space-change".to_string(),
-        "--ignore-all-space".to_string(),
-        "--ignore-submodules".to_string(),
-        "--no-color".to_string(),
-        "--find-copies-harder".to_string(),
-        "-M".to_string(),
-        "--diff-filter=ACDM".to_string(),
-    ];
-
-    let patterns = compile_patterns(&params.excludes);
-    let cached = state.cache.load(params)?;
-    if let Some(CacheEntry::Cached {
-        head: cached_head, ..
+    let cached = match state.cache.load(params)? {
+        // e.g. `from` is a branch that moved
+        Some(CacheEntry::Cached {
+            base: ref cached_base,
+            ..
+        }) if *cached_base != base.map(|base| base.to_string()) => {
+            info!("start of the range changed, recalculating");
+            None
+        }
+        Some(CacheEntry::Cached {
+            branches: ref cached_branches,
+            ..
+        }) if !contains_branches(&repo, &branches, cached_branches) => {
+            info!("branch deleted or rewritten, recalculating");
+            None
+        }
+        Some(CacheEntry::Cached { details: None, .. }) if params.details => {
+            info!("details requested, recalculating");
+            None
+        }
+        cached => cached,
+    };
+    // entries that kept their details so far must keep them up to date
+    let details = params.details
+        || matches!(
+            cached,
+            Some(CacheEntry::Cached {
+                details: Some(_),
+                ..
+            })
+        );
+    let from = if let Some(CacheEntry::Cached {
+        head: cached_head,
+        branches: cached_branches,
+        ..
     }) = cached.as_ref()
     {
         debug!("using cache");
-        if cached_head == &head {
+        if *cached_head == head.to_string() && *cached_branches == branch_names {
             trace!("cache up to date");
             return Ok(());
         }
         trace!("updating cache");
-        arg.push(format!("{head}..{branch}"));
-        arg_commit_count.push(format!("{head}..{branch}"));
+        if cached_branches.is_empty() {
+            vec![Oid::from_str(cached_head)?]
+        } else {
+            cached_branches
+                .iter()
+                .map(|branch| Oid::from_str(branch))
+                .collect::<std::result::Result<_, _>>()?
+        }
     } else {
         debug!("Creating cache");
-        arg.push(branch.clone());
-        arg_commit_count.push(branch.clone());
-    }
+        Vec::new()
+    };
+    let tips = if branches.is_empty() {
+        vec![head]
+    } else {
+        branches
+    };
 
-    arg.push("--".to_string());
-    arg.push(".".to_string());
+    let patterns = compile_patterns(&params.options.excludes);
+    let includes = compile_patterns(&params.options.includes);
+    let ignore_attributes = params.options.ignore_attributes;
+    let diff_settings = params.options.diff;
+    let numstat_file = params.numstat_file(&state.settings);
+    let submodules = if params.options.recurse_submodules {
+        let pinned = pinned_submodules(&repo, head, "", None, cached.as_ref())?;
+        open_submodules(params, state, pinned, cached.as_ref()).await
+    } else {
+        Vec::new()
+    };
+    let entry = {
+        let state = Arc::clone(state);
+        tokio::task::spawn_blocking(move || {
+            let rules = if ignore_attributes {
+                Vec::new()
+            } else {
+                attributes::rules(&repo, &repo.find_commit(head)?.tree()?)?
+            };
+            let (cached, from) = match cached {
+                Some(CacheEntry::Cached { ref attributes, .. }) if *attributes != rules => {
+                    info!("attributes changed, recalculating");
+                    (None, Vec::new())
+                }
+                cached => (cached, from),
+            };
+            let attributes = Attributes::new(&rules);
 
-    let repo_path = params.repo(&state.settings);
+            let changes = Changes {
+                details: details.then(Details::default),
+                attributes: rules,
+                base: base.map(|base| base.to_string()),
+                branches: branch_names,
+                ..Changes::default()
+            };
+            // the cached submodule heads are only valid, if the cache is updated
+            let cache_kept = !from.is_empty();
+            let hide: Vec<_> = from.into_iter().chain(base).collect();
+            let cancelled = || state.queue.is_closed();
+            let mut counter = Counter {
+                changes,
+                index: HashMap::new(),
+                patterns: &patterns,
+                includes: &includes,
+                attributes: &attributes,
+                diff_settings,
+            };
+            let mut store = CommitStore::open(numstat_file)?;
+            let commits = numstat(
+                &repo,
+                &hide,
+                &tips,
+                diff_settings,
+                &mut store,
+                // stop when shutting down
+                cancelled,
+                |commit, files| {
+                    counter.count(commit, files, "");
+                },
+            );
+            // keep the diffed commits, even if the walk was cancelled
+            store.save()?;
+            counter.changes.commits = commits?;
 
-    // TODO: this is also kinda blocking but should be fast enough
-    let output = Command::new("git")
-        .args(&arg)
-        .current_dir(&repo_path)
-        .output()?
-        .stdout;
-    let output = String::from_utf8_lossy(&output);
-
-    // TODO: this is also kinda blocking but should be fast enough
-    let output_commits = Command::new("git")
-        .args(&arg_commit_count)
-        .current_dir(&repo_path)
-        .output()?
-        .stdout;
-    let output_commits = String::from_utf8_lossy(&output_commits);
-
-    let commits: u64 = output_commits.trim().parse()?;
-    let count: u64 = output.lines().fold(0, |sum, line| {
-        let mut parts = line.split_whitespace();
-        let additions = parts.next();
-        let deletions = parts.next();
-        let file_path = parts.next().unwrap_or_default();
-
-        if matches(file_path, &patterns) {
-            sum
-        } else {
-            let additions: u64 = additions.and_then(|s| s.parse().ok()).unwrap_or_default();
-            let deletions: u64 = deletions.and_then(|s| s.parse().ok()).unwrap_or_default();
+            // start of the counted range of every submodule, nested submodules are counted from
+            // the commit pinned at the start of their parent
+            let mut starts = Vec::with_capacity(submodules.len());
+            for idx in 0..submodules.len() {
+                let submodule = &submodules[idx];
+                let (parent_repo, parent_head, parent_start) = match submodule.parent {
+                    Some(parent) => (
+                        &submodules[parent].repo,
+                        submodules[parent].pinned,
+                        starts[parent],
+                    ),
+                    None => (&repo, head, base),
+                };
+                let start = match submodule.cached.filter(|_| cache_kept) {
+                    Some(cached) => Some(cached),
+                    None => submodule::range_start(
+                        parent_repo,
+                        parent_start,
+                        parent_head,
+                        &submodule.local_path,
+                    )?,
+                }
+                // e.g. the history of the submodule was rewritten
+                .filter(|start| submodule.repo.find_commit(*start).is_ok());
+                starts.push(start);
 
-            sum + additions + deletions
-        }
-    });
+                let prefix = format!("{}/", submodule.path);
+                let mut lines = Lines::default();
+                let mut store = CommitStore::open(submodule.numstat_file.clone())?;
+                let commits = numstat(
+                    &submodule.repo,
+                    &start.into_iter().collect::<Vec<_>>(),
+                    &[submodule.pinned],
+                    diff_settings,
+                    &mut store,
+                    cancelled,
+                    |commit, files| lines.add(counter.count(commit, files, &prefix)),
+                );
+                store.save()?;
+                let commits = commits?;
+                counter.changes.commits += commits;
+                counter.changes.submodules.push(SubmoduleStats {
+                    path: submodule.path.clone(),
+                    url: submodule.url.clone(),
+                    head: submodule.pinned.to_string(),
+                    additions: lines.additions,
+                    deletions: lines.deletions,
+                    commits,
+                });
+            }
+            let changes = counter.changes;
 
-    let cached = cached.map_or_else(
-        || CacheEntry::Cached {
-            head: head.clone(),
-            count,
-            commits,
-        },
-        |c| c.update(count, commits, &head),
-    );
-    state.cache.store(params.clone(), cached.clone())?;
+            let entry = cached
+                .unwrap_or(CacheEntry::NotFound)
+                .update(changes, &head.to_string());
+            let mailmap = mailmap(&repo, head)?;
+            Ok::<_, Error>(entry.resolve_authors(|name, email| {
+                Signature::now(name, email)
+                    .and_then(|sig| mailmap.resolve_signature(&sig))
+                    .map_or_else(
+                        |_| (name.to_string(), email.to_string()),
+                        |sig| {
+                            (
+                                String::from_utf8_lossy(sig.name_bytes()).into_owned(),
+                                String::from_utf8_lossy(sig.email_bytes()).into_owned(),
+                            )
+                        },
+                    )
+            }))
+        })
+    }
+    .await??;
+    state.cache.store(params.clone(), entry)?;
 
     Ok(())
 }
 
-fn compile_patterns(excludes: &Excludes) -> Vec<Pattern> {
-    excludes
+/// Adds the stats of commits to [`Changes`], skipping excluded files.
+struct Counter<'a> {
+    changes: Changes,
+    /// Position of each identity in the identities of `changes.details`
+    index: HashMap<(String, String), usize>,
+    patterns: &'a [Pattern],
+    includes: &'a [Pattern],
+    attributes: &'a Attributes,
+    diff_settings: DiffSettings,
+}
+
+impl Counter<'_> {
+    /// Count the `files` changed by `commit`. `prefix` is prepended to their paths, e.g. the path
+    /// of a submodule.
+    ///
+    /// Returns the counted lines.
+    fn count(&mut self, commit: &Commit<'_>, files: &[FileStat], prefix: &str) -> Lines {
+        let changes = &mut self.changes;
+        let mut timeline_entry = TimelineEntry {
+            time: commit.time().seconds(),
+            additions: 0,
+            deletions: 0,
+            binary_changes: 0,
+        };
+        for file in files {
+            let path = format!("{prefix}{}", file.path);
+            if !(self.includes.is_empty() || matches(&path, self.includes))
+                || matches(&path, self.patterns)
+                || self.attributes.excluded(&path)
+            {
+                continue;
+            }
+            let weight = self.diff_settings.binary_weight;
+            let lines = if file.binary {
+                changes.binary_changes += 1;
+                timeline_entry.binary_changes += 1;
+                if self.diff_settings.binary != Binary::Count {
+                    continue;
+                }
+                Lines {
+                    additions: file.additions.saturating_mul(weight),
+                    deletions: file.deletions.saturating_mul(weight),
+                }
+            } else {
+                Lines {
+                    additions: file.additions,
+                    deletions: file.deletions,
+                }
+            };
+            // the weight of binary files can make the sums overflow
+            changes.additions = changes.additions.saturating_add(lines.additions);
+            changes.deletions = changes.deletions.saturating_add(lines.deletions);
+            timeline_entry.additions = timeline_entry.additions.saturating_add(lines.additions);
+            timeline_entry.deletions = timeline_entry.deletions.saturating_add(lines.deletions);
+            if let Some(details) = &mut changes.details {
+                details.files.entry(path).or_default().add(lines);
+            }
+        }
+        if let Some(details) = &mut changes.details {
+            let author = commit.author();
+            let key = (
+                String::from_utf8_lossy(author.name_bytes()).into_owned(),
+                String::from_utf8_lossy(author.email_bytes()).into_owned(),
+            );
+            let idx = *self.index.entry(key).or_insert_with_key(|(name, email)| {
+                details
+                    .identities
+                    .push(Author::new(name.clone(), email.clone()));
+                details.identities.len() - 1
+            });
+            let identity = &mut details.identities[idx];
+            identity.commits += 1;
+            identity.additions = identity.additions.saturating_add(timeline_entry.additions);
+            identity.deletions = identity.deletions.saturating_add(timeline_entry.deletions);
+            details.timeline.push(timeline_entry);
+        }
+        Lines {
+            additions: timeline_entry.additions,
+            deletions: timeline_entry.deletions,
+        }
+    }
+}
+
+/// A submodule declared in the `.gitmodules` of the counted repository or of another submodule.
+struct PinnedSubmodule {
+    submodule: Submodule,
+    /// Path in the counted repository, including the paths of the parent submodules
+    path: String,
+    /// Commit pinned at the counted head
+    head: Oid,
+    /// Commit the cached entry counted the submodule up to
+    cached: Option<Oid>,
+    /// Index of the parent submodule in the opened submodules, `None` for submodules of the
+    /// counted repository
+    parent: Option<usize>,
+}
+
+/// A submodule of the counted repository or of another submodule, opened through [`open_repo`].
+struct OpenSubmodule {
+    /// Path in the counted repository, including the paths of the parent submodules
+    path: String,
+    /// Path in the parent repository
+    local_path: String,
+    url: String,
+    id: RepoId,
+    repo: Repository,
+    numstat_file: PathBuf,
+    /// Commit pinned at the counted head
+    pinned: Oid,
+    /// Commit the cached entry counted the submodule up to
+    cached: Option<Oid>,
+    /// Index of the parent submodule, `None` for submodules of the counted repository
+    parent: Option<usize>,
+}
+
+/// Find the submodules declared in `.gitmodules` at `head` and the commits they are pinned to.
+///
+/// `prefix` is the path of the repository in the counted repository, `parent` its index in the
+/// opened submodules.
+fn pinned_submodules(
+    repo: &Repository,
+    head: Oid,
+    prefix: &str,
+    parent: Option<usize>,
+    cached: Option<&CacheEntry>,
+) -> Result<Vec<PinnedSubmodule>> {
+    let mut pinned = Vec::new();
+    for submodule in submodule::submodules(repo, head)? {
+        let path = format!("{prefix}{}", submodule.path);
+        let Some(commit) = submodule::pinned(repo, head, &submodule.path)? else {
+            warn!(path, "submodule is not checked in");
+            continue;
+        };
+        let cached = match cached {
+            Some(CacheEntry::Cached { submodules, .. }) => submodules
+                .iter()
+                .find(|s| s.path == path && s.url == submodule.url)
+                .and_then(|s| Oid::from_str(&s.head).ok()),
+            _ => None,
+        };
+        pinned.push(PinnedSubmodule {
+            submodule,
+            path,
+            head: commit,
+            cached,
+            parent,
+        });
+    }
+    Ok(pinned)
+}
+
+/// Clone or fetch the `pinned` submodules and the submodules nested in them.
+///
+/// Nested submodules are read from the `.gitmodules` at the pinned commit of their parent.
+/// Submodules that cannot be resolved or opened are skipped with a warning, so they are counted
+/// from their cached head on the next update. Parents are always opened before their submodules.
+async fn open_submodules(
+    params: &HocParams,
+    state: &AppState,
+    pinned: Vec<PinnedSubmodule>,
+    cached: Option<&CacheEntry>,
+) -> Vec<OpenSubmodule> {
+    let mut submodules: Vec<OpenSubmodule> = Vec::new();
+    let mut queue = VecDeque::from(pinned);
+    while let Some(PinnedSubmodule {
+        submodule,
+        path,
+        head: commit,
+        cached: cached_head,
+        parent,
+    }) = queue.pop_front()
+    {
+        let parent_id = parent.map_or(&params.id, |idx| &submodules[idx].id);
+        let id = match state.settings.submodule_repo(parent_id, &submodule.url) {
+            Ok(id) => id,
+            Err(err) => {
+                warn!(path, url = submodule.url, %err, "cannot resolve submodule");
+                continue;
+            }
+        };
+        // a repository that contains itself, e.g. at an older commit
+        let mut ancestor = parent;
+        let mut cyclic = id == params.id;
+        while let Some(idx) = ancestor {
+            cyclic |= submodules[idx].id == id;
+            ancestor = submodules[idx].parent;
+        }
+        if cyclic {
+            warn!(path, "skipping submodule that contains itself");
+            continue;
+        }
+        // the stats of the submodule commits are shared with requests for the submodule itself
+        let submodule_params = HocParams::new(
+            id,
+            None,
+            CountOptions {
+                diff: params.options.diff,
+                ..CountOptions::default()
+            },
+        );
+        let repo = match open_repo(&submodule_params, state).await {
+            Ok(Some(repo)) => repo,
+            Ok(None) => {
+                warn!(path, "submodule not found");
+                continue;
+            }
+            Err(err) => {
+                warn!(path, %err, "cannot open submodule");
+                continue;
+            }
+        };
+        if repo.find_commit(commit).is_err() {
+            warn!(path, pinned = %commit, "pinned commit of submodule not found");
+            continue;
+        }
+        match pinned_submodules(
+            &repo,
+            commit,
+            &format!("{path}/"),
+            Some(submodules.len()),
+            cached,
+        ) {
+            Ok(nested) => queue.extend(nested),
+            Err(err) => warn!(path, %err, "cannot read nested submodules"),
+        }
+        submodules.push(OpenSubmodule {
+            path,
+            local_path: submodule.path,
+            url: submodule.url,
+            numstat_file: submodule_params.numstat_file(&state.settings),
+            id: submodule_params.id,
+            repo,
+            pinned: commit,
+            cached: cached_head,
+            parent,
+        });
+    }
+    submodules
+}
+
+/// Load the `.mailmap` of the counted commit.
+///
+/// Bare repositories would only read the `.mailmap` of `HEAD`, which is not necessarily the
+/// requested branch.
+fn mailmap(repo: &Repository, head: Oid) -> Result<Mailmap> {
+    let tree = repo.find_commit(head)?.tree()?;
+    let Ok(entry) = tree.get_path(Path::new(".mailmap")) else {
+        return Ok(Mailmap::new()?);
+    };
+    let Ok(blob) = entry.to_object(repo)?.peel_to_blob() else {
+        return Ok(Mailmap::new()?);
+    };
+    Ok(Mailmap::from_buffer(&String::from_utf8_lossy(
+        blob.content(),
+    ))?)
+}
+
+fn compile_patterns(patterns: &BTreeSet<String>) -> Vec<Pattern> {
+    patterns
         .iter()
         .filter_map(|pattern| {
             let pattern = pattern.trim();
@@ -206,3 +1009,71 @@ fn matches(file_path: &str, patterns: &[Pattern]) -> bool {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::redirect_target;
+
+    use reqwest::Url;
+
+    fn target(origin: &str, target: &str, owner: &str, repo: &str) -> Option<(String, String)> {
+        redirect_target(
+            &Url::parse(origin).unwrap(),
+            &Url::parse(target).unwrap(),
+            owner,
+            repo,
+        )
+    }
+
+    #[test]
+    fn renamed_repo() {
+        assert_eq!(
+            target(
+                "https://github.com/owner/old",
+                "https://github.com/owner/new.git/info/refs?service=git-upload-pack",
+                "owner",
+                "old"
+            ),
+            Some(("owner".to_string(), "new".to_string()))
+        );
+    }
+
+    #[test]
+    fn transferred_to_subgroup() {
+        assert_eq!(
+            target(
+                "https://git.example.com/git/owner/repo.git",
+                "https://git.example.com/git/group/sub/repo.git/info/refs?service=git-upload-pack",
+                "owner",
+                "repo"
+            ),
+            Some(("group/sub".to_string(), "repo".to_string()))
+        );
+    }
+
+    #[test]
+    fn no_redirect() {
+        assert_eq!(
+            target(
+                "https://github.com/Owner/Repo",
+                "https://github.com/owner/repo/info/refs?service=git-upload-pack",
+                "Owner",
+                "Repo"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn other_host() {
+        assert_eq!(
+            target(
+                "https://github.com/owner/repo",
+                "https://example.com/owner/other/info/refs?service=git-upload-pack",
+                "owner",
+                "repo"
+            ),
+            None
+        );
+    }
+}
@@ -1,14 +1,26 @@
 use crate::{
-    cache::{Cache, CacheEntry, Excludes, HocParams},
-    error::Result,
+    cache::{
+        ALL_BRANCHES, Author, Cache, CacheEntry, CountOptions, Details, Excludes, HocParams,
+        Includes, Lines, SubmoduleStats,
+    },
+    diff::{
+        Binary, DEFAULT_BINARY_WEIGHT, DiffSettings, MAX_BINARY_WEIGHT, Merges, Renames, Whitespace,
+    },
+    error::{Error, Result},
+    history::{self, Bucket, Point},
+    hoc::is_valid_revision,
     http::AppState,
+    language,
     platform::Platform,
+    repo_id::RepoId,
     statics::VERSION_INFO,
-    template::RepoInfo,
+    template::{LanguageShare, RepoInfo},
     templates,
+    window::Window,
 };
 
 use std::{
+    collections::BTreeMap,
     io,
     sync::{Arc, atomic::Ordering},
 };
@@ -20,7 +32,7 @@ use axum::{
         StatusCode,
         header::{self, HeaderMap, HeaderValue},
     },
-    response::{IntoResponse, Redirect},
+    response::{IntoResponse, Redirect, Response},
 };
 use badgers::{Badge, BadgeOptions};
 use jiff::{SignedDuration, Timestamp, fmt::rfc2822};
@@ -33,22 +45,275 @@ use unit_prefix::NumberPrefix;
 struct JsonResponse<'a> {
     head: &'a str,
     branch: &'a str,
+    /// Sum of `additions` and `deletions`
     count: u64,
+    additions: u64,
+    deletions: u64,
     commits: u64,
+    /// Number of changed binary files, only reported with `binary=report`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    binary_changes: Option<u64>,
+    /// HoC per submodule, only reported with `submodules=recurse`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    submodules: Option<Vec<SubmoduleResponse<'a>>>,
 }
 
-const DEFAULT_LABEL: &str = "Hits-of-Code";
+#[derive(Serialize)]
+struct SubmoduleResponse<'a> {
+    path: &'a str,
+    url: &'a str,
+    /// Commit the submodule is pinned to
+    head: &'a str,
+    /// Sum of `additions` and `deletions`
+    count: u64,
+    additions: u64,
+    deletions: u64,
+    commits: u64,
+}
+
+impl<'a> From<&'a SubmoduleStats> for SubmoduleResponse<'a> {
+    fn from(submodule: &'a SubmoduleStats) -> Self {
+        Self {
+            path: &submodule.path,
+            url: &submodule.url,
+            head: &submodule.head,
+            count: submodule.additions.saturating_add(submodule.deletions),
+            additions: submodule.additions,
+            deletions: submodule.deletions,
+            commits: submodule.commits,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AuthorResponse<'a> {
+    name: &'a str,
+    email: &'a str,
+    /// Sum of `additions` and `deletions`
+    count: u64,
+    additions: u64,
+    deletions: u64,
+    commits: u64,
+}
+
+impl<'a> From<&'a Author> for AuthorResponse<'a> {
+    fn from(author: &'a Author) -> Self {
+        Self {
+            name: &author.name,
+            email: &author.email,
+            count: author.hoc(),
+            additions: author.additions,
+            deletions: author.deletions,
+            commits: author.commits,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AuthorsResponse<'a> {
+    head: &'a str,
+    branch: &'a str,
+    /// Authors, resolved through the `.mailmap` and sorted by HoC
+    authors: Vec<AuthorResponse<'a>>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct DirectoryResponse {
+    /// Directory prefix, `.` for files in the repository root
+    path: String,
+    /// Sum of `additions` and `deletions`
+    count: u64,
+    additions: u64,
+    deletions: u64,
+}
+
+#[derive(Serialize)]
+struct TreeResponse<'a> {
+    head: &'a str,
+    branch: &'a str,
+    depth: usize,
+    /// Directories sorted by HoC
+    directories: Vec<DirectoryResponse>,
+}
+
+#[derive(Serialize)]
+struct LanguageResponse {
+    name: &'static str,
+    /// Sum of `additions` and `deletions`
+    count: u64,
+    additions: u64,
+    deletions: u64,
+}
+
+#[derive(Serialize)]
+struct LanguagesResponse<'a> {
+    head: &'a str,
+    branch: &'a str,
+    /// Languages sorted by HoC
+    languages: Vec<LanguageResponse>,
+}
+
+#[derive(Serialize)]
+struct HistoryResponse<'a> {
+    head: &'a str,
+    branch: &'a str,
+    bucket: &'static str,
+    /// Buckets sorted by date
+    history: Vec<Point>,
+}
+
+/// Default number of path segments used to group files in the `/tree` endpoint
+const DEFAULT_TREE_DEPTH: usize = 1;
+
+/// Number of authors listed on the overview page
+const OVERVIEW_AUTHORS: usize = 10;
+
+/// Value shown on the badge.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Metric {
+    /// Added and deleted lines
+    #[default]
+    Hoc,
+    Additions,
+    Deletions,
+}
+
+impl Metric {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Hoc => "Hits-of-Code",
+            Self::Additions => "Additions",
+            Self::Deletions => "Deletions",
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Hoc => "hoc",
+            Self::Additions => "additions",
+            Self::Deletions => "deletions",
+        }
+    }
+}
+
+/// Branches to count, takes precedence over the `branch` parameter.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Branches {
+    /// Count the union of all branches, like `branch=*`
+    All,
+}
+
+impl Branches {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::All => "all",
+        }
+    }
+}
+
+/// Handling of submodules.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Submodules {
+    /// Submodules are not counted, like `git log --ignore-submodules`
+    #[default]
+    Ignore,
+    /// Add the HoC of the submodules over the commits pinned in the counted range
+    Recurse,
+}
+
+impl Submodules {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Ignore => "ignore",
+            Self::Recurse => "recurse",
+        }
+    }
+}
+
+/// Response format of the `/history` endpoint.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    #[default]
+    Json,
+    Csv,
+}
 
 #[derive(Deserialize, Debug)]
 pub(crate) struct BadgeQuery {
+    /// Remote URL for the `/git` endpoints
+    url: Option<String>,
     branch: Option<String>,
+    /// Set to `all` to count all branches
+    branches: Option<Branches>,
     exclude: Option<String>,
+    /// Only count files matching these patterns
+    include: Option<String>,
     label: Option<String>,
+    metric: Option<Metric>,
+    /// Number of directory levels in the `/tree` endpoint
+    depth: Option<usize>,
+    /// Bucket size in the `/history` endpoint
+    bucket: Option<Bucket>,
+    /// Response format of the `/history` endpoint
+    format: Option<Format>,
+    /// Set to `false` to count files that are excluded by `.gitattributes`
+    attributes: Option<bool>,
+    /// Only count commits since this time
+    since: Option<String>,
+    /// Only count commits until this time
+    until: Option<String>,
+    /// Only count commits of the last period, e.g. `30d`
+    last: Option<String>,
+    /// Only count commits that are not reachable from this revision
+    from: Option<String>,
+    /// Count the commits reachable from this revision instead of the branch
+    to: Option<String>,
+    /// Override the default whitespace handling
+    whitespace: Option<Whitespace>,
+    /// Override the default rename detection
+    renames: Option<Renames>,
+    /// Override the default merge handling
+    merges: Option<Merges>,
+    /// Override the default binary file handling
+    binary: Option<Binary>,
+    /// Override the default number of lines a changed binary file counts as
+    binary_weight: Option<u64>,
+    /// Set to `recurse` to count submodules
+    submodules: Option<Submodules>,
 }
 
 impl BadgeQuery {
+    /// Requested branch, [`ALL_BRANCHES`] if `branches=all` is passed.
+    fn branch(&self) -> Option<String> {
+        if self.branches == Some(Branches::All) {
+            Some(ALL_BRANCHES.to_string())
+        } else {
+            self.branch.clone()
+        }
+    }
+
+    /// Name of the counted branch for humans.
+    fn branch_name(&self) -> &str {
+        if self.branches == Some(Branches::All) {
+            return "all branches";
+        }
+        match self.branch.as_deref() {
+            Some(ALL_BRANCHES) => "all branches",
+            Some(branch) => branch,
+            None => "default branch",
+        }
+    }
+
     fn label(&self) -> &str {
-        self.label.as_deref().unwrap_or(DEFAULT_LABEL)
+        self.label.as_deref().unwrap_or(self.metric().label())
+    }
+
+    fn metric(&self) -> Metric {
+        self.metric.unwrap_or_default()
     }
 
     fn excludes(&self) -> Excludes {
@@ -58,16 +323,131 @@ impl BadgeQuery {
             .unwrap_or_default()
     }
 
+    fn includes(&self) -> Includes {
+        self.include
+            .as_ref()
+            .map(|i| {
+                i.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn window(&self) -> Result<Window> {
+        Window::parse(
+            self.since.as_deref(),
+            self.until.as_deref(),
+            self.last.as_deref(),
+            Timestamp::now(),
+        )
+    }
+
+    /// Count options of the request, falling back to the configured `defaults` for the diff.
+    fn options(&self, defaults: DiffSettings) -> Result<CountOptions> {
+        if let Some(weight) = self.binary_weight
+            && weight > MAX_BINARY_WEIGHT
+        {
+            return Err(Error::InvalidQuery(format!(
+                "`binary_weight` must not be larger than {MAX_BINARY_WEIGHT}"
+            )));
+        }
+        if let Some(revision) = [&self.from, &self.to]
+            .into_iter()
+            .flatten()
+            .find(|revision| !is_valid_revision(revision))
+        {
+            return Err(Error::InvalidRevision(revision.clone()));
+        }
+        let binary = self.binary.unwrap_or(defaults.binary);
+        Ok(CountOptions {
+            excludes: self.excludes(),
+            includes: self.includes(),
+            ignore_attributes: self.attributes == Some(false),
+            from: self.from.clone(),
+            to: self.to.clone(),
+            diff: DiffSettings {
+                whitespace: self.whitespace.unwrap_or(defaults.whitespace),
+                renames: self.renames.unwrap_or(defaults.renames),
+                merges: self.merges.unwrap_or(defaults.merges),
+                binary,
+                // the weight only changes the result if binary files are counted
+                binary_weight: if binary == Binary::Count {
+                    self.binary_weight.unwrap_or(defaults.binary_weight)
+                } else {
+                    DEFAULT_BINARY_WEIGHT
+                },
+            },
+            recurse_submodules: self.submodules == Some(Submodules::Recurse),
+        })
+    }
+
     fn to_query(&self) -> String {
+        let url = self
+            .url
+            .as_ref()
+            .map(|u| format!("url={}", urlencoding::encode(u)));
         let branch = self.branch.as_ref().map(|b| format!("branch={b}"));
+        let branches = self.branches.map(|b| format!("branches={}", b.as_str()));
         let exclude = self.exclude.as_ref().map(|e| format!("exclude={e}"));
+        let include = self.include.as_ref().map(|i| format!("include={i}"));
         let label = self.label.as_ref().map(|l| format!("label={l}"));
+        let metric = self.metric.map(|m| format!("metric={}", m.as_str()));
+        let attributes = self.attributes.map(|a| format!("attributes={a}"));
+        let since = self
+            .since
+            .as_ref()
+            .map(|s| format!("since={}", urlencoding::encode(s)));
+        let until = self
+            .until
+            .as_ref()
+            .map(|u| format!("until={}", urlencoding::encode(u)));
+        let last = self.last.as_ref().map(|l| format!("last={l}"));
+        let from = self
+            .from
+            .as_ref()
+            .map(|f| format!("from={}", urlencoding::encode(f)));
+        let to = self
+            .to
+            .as_ref()
+            .map(|t| format!("to={}", urlencoding::encode(t)));
+        let whitespace = self
+            .whitespace
+            .map(|w| format!("whitespace={}", w.as_str()));
+        let renames = self.renames.map(|r| format!("renames={}", r.as_str()));
+        let merges = self.merges.map(|m| format!("merges={}", m.as_str()));
+        let binary = self.binary.map(|b| format!("binary={}", b.as_str()));
+        let binary_weight = self.binary_weight.map(|w| format!("binary_weight={w}"));
+        let submodules = self
+            .submodules
+            .map(|s| format!("submodules={}", s.as_str()));
 
-        let query = [branch, exclude, label]
-            .into_iter()
-            .flatten()
-            .collect::<Vec<_>>()
-            .join("&");
+        let query = [
+            url,
+            branch,
+            branches,
+            exclude,
+            include,
+            label,
+            metric,
+            attributes,
+            since,
+            until,
+            last,
+            from,
+            to,
+            whitespace,
+            renames,
+            merges,
+            binary,
+            binary_weight,
+            submodules,
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("&");
 
         if query.is_empty() {
             String::new()
@@ -77,16 +457,185 @@ impl BadgeQuery {
     }
 }
 
+/// Action requested for a repository, taken from the last segment of the request path.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Action {
+    Badge,
+    Json,
+    Authors,
+    Tree,
+    Languages,
+    History,
+    View,
+    Delete,
+}
+
+/// Split the path following the platform into repository and requested action.
+///
+/// The owner may consist of multiple segments (e.g. GitLab subgroups), so
+/// `group/subgroup/project/view` is parsed as owner `group/subgroup`, repository `project` and
+/// action [`Action::View`]. The last segment is only used as action if at least two segments
+/// remain.
+fn split_repo_path(platform: Platform, path: &str) -> Result<(RepoId, Action)> {
+    let mut segments: Vec<_> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let action = match segments.as_slice() {
+        [_, _, .., "json"] => Action::Json,
+        [_, _, .., "authors"] => Action::Authors,
+        [_, _, .., "tree"] => Action::Tree,
+        [_, _, .., "languages"] => Action::Languages,
+        [_, _, .., "history"] => Action::History,
+        [_, _, .., "view"] => Action::View,
+        [_, _, .., "delete"] => Action::Delete,
+        _ => Action::Badge,
+    };
+    if action != Action::Badge {
+        segments.pop();
+    }
+    match segments.split_last() {
+        Some((repo, owner)) if !owner.is_empty() => {
+            Ok((RepoId::new(platform, owner.join("/"), *repo)?, action))
+        }
+        _ => Err(Error::InvalidRepo(path.to_string())),
+    }
+}
+
+pub(crate) async fn get_repo(
+    State(state): State<Arc<AppState>>,
+    ReqPath((platform, path)): ReqPath<(String, String)>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response> {
+    let platform = state.settings.platform(&platform)?;
+    let (id, action) = split_repo_path(platform, &path)?;
+    if action == Action::Delete {
+        Ok(StatusCode::METHOD_NOT_ALLOWED.into_response())
+    } else {
+        handle_action(state, id, action, query).await
+    }
+}
+
+pub(crate) async fn post_repo(
+    State(state): State<Arc<AppState>>,
+    ReqPath((platform, path)): ReqPath<(String, String)>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response> {
+    let platform = state.settings.platform(&platform)?;
+    let (id, action) = split_repo_path(platform, &path)?;
+    if action == Action::Delete {
+        handle_action(state, id, action, query).await
+    } else {
+        Ok(StatusCode::METHOD_NOT_ALLOWED.into_response())
+    }
+}
+
+async fn git_action(state: Arc<AppState>, query: BadgeQuery, action: Action) -> Result<Response> {
+    let url = query
+        .url
+        .as_deref()
+        .ok_or_else(|| Error::InvalidRepo(String::new()))?;
+    let id = state.settings.git_repo(url)?;
+    handle_action(state, id, action, query).await
+}
+
+pub(crate) async fn git_badge(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response> {
+    git_action(state, query, Action::Badge).await
+}
+
+pub(crate) async fn git_json(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response> {
+    git_action(state, query, Action::Json).await
+}
+
+pub(crate) async fn git_authors(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response> {
+    git_action(state, query, Action::Authors).await
+}
+
+pub(crate) async fn git_tree(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response> {
+    git_action(state, query, Action::Tree).await
+}
+
+pub(crate) async fn git_languages(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response> {
+    git_action(state, query, Action::Languages).await
+}
+
+pub(crate) async fn git_history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response> {
+    git_action(state, query, Action::History).await
+}
+
+pub(crate) async fn git_overview(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response> {
+    git_action(state, query, Action::View).await
+}
+
+pub(crate) async fn git_delete(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response> {
+    git_action(state, query, Action::Delete).await
+}
+
+async fn handle_action(
+    state: Arc<AppState>,
+    id: RepoId,
+    action: Action,
+    query: BadgeQuery,
+) -> Result<Response> {
+    // renamed repositories are served from their canonical location
+    let requested = id;
+    let id = state.cache.resolve_alias(requested.clone())?;
+    Ok(match action {
+        Action::Badge => calculate_hoc(&state, id, &query).await?.into_response(),
+        Action::Json => json_hoc(&state, id, &query).await?.into_response(),
+        Action::Authors => json_authors(&state, id, &query).await?.into_response(),
+        Action::Tree => json_tree(&state, id, &query).await?.into_response(),
+        Action::Languages => json_languages(&state, id, &query).await?.into_response(),
+        Action::History => history(&state, id, &query).await?.into_response(),
+        Action::View => overview(&state, id, &query).await?.into_response(),
+        Action::Delete => delete_repo_and_cache(&state, &requested, id, &query)?.into_response(),
+    })
+}
+
 enum HocResult {
     Hoc {
-        hoc: u64,
+        additions: u64,
+        deletions: u64,
+        commits: u64,
+        /// Number of changed binary files
+        binary_changes: u64,
+        head: String,
+        /// Authors, files and timeline of the cache entry
+        details: Arc<Details>,
+        /// HoC per submodule, included in the totals
+        submodules: Vec<SubmoduleStats>,
+        params: Box<HocParams>,
+    },
+    /// Entry of an older version that is counted again, only the HoC is known
+    Outdated {
+        count: u64,
         commits: u64,
-        hoc_pretty: String,
         head: String,
-        params: HocParams,
     },
     Loading,
     NotFound,
+    Unauthorized,
 }
 
 #[instrument(
@@ -94,36 +643,52 @@ enum HocResult {
     skip_all,
     fields(platform, owner, repo, branch)
 )]
-pub(crate) async fn delete_repo_and_cache(
-    State(state): State<Arc<AppState>>,
-    ReqPath((platform, owner, repo)): ReqPath<(Platform, String, String)>,
-    Query(branch): Query<BadgeQuery>,
+fn delete_repo_and_cache(
+    state: &AppState,
+    requested: &RepoId,
+    id: RepoId,
+    query: &BadgeQuery,
 ) -> Result<impl IntoResponse> {
     info!("Deleting cache and repository");
-    let repo_dir = state
-        .repos()
-        .join(format!("{}/{owner}/{repo}", platform.domain()));
-    std::fs::remove_dir_all(repo_dir).or_else(|e| {
-        if e.kind() == io::ErrorKind::NotFound {
-            Ok(())
-        } else {
-            Err(e)
-        }
-    })?;
-    state.repo_count.fetch_sub(1, Ordering::Relaxed);
+    // local repositories are not managed by us, so only the cache is cleared
+    if *id.platform() != Platform::Local {
+        std::fs::remove_dir_all(id.path(state.repos())).or_else(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        })?;
+        state.repo_count.fetch_sub(1, Ordering::Relaxed);
+    }
 
-    state.cache.clear(platform, &owner, &repo)?;
+    state.cache.clear(&id)?;
+    if *requested != id {
+        // removes the alias of the old location
+        state.cache.clear(requested)?;
+    }
 
-    let query = branch.to_query();
+    let params = HocParams::new(id, None, CountOptions::default());
     Ok(Redirect::to(&format!(
-        "{}/{}/{owner}/{repo}/view{query}",
+        "{}/{}/view{}",
         state.settings.base_url,
-        platform.url_path()
+        params.service_path(),
+        query.to_query()
     )))
 }
 
-#[instrument(skip_all, fields(platform = params.platform.domain(), owner=params.owner, repo=params.repo, branch=params.branch))]
-async fn handle_hoc_request(state: &AppState, params: &HocParams) -> Result<HocResult> {
+#[instrument(skip_all, fields(platform = params.id.platform().domain(), owner = params.id.owner(), repo = params.id.repo(), branch = params.branch))]
+async fn handle_hoc_request(
+    state: &AppState,
+    params: &HocParams,
+    window: &Window,
+) -> Result<HocResult> {
+    // the lines in a time window are counted from the timeline
+    let params = &if window.is_all() {
+        params.clone()
+    } else {
+        params.clone().with_details()
+    };
     let queued = state.queue.push(params.clone());
     if queued {
         trace!("queued new calculation job");
@@ -133,54 +698,106 @@ async fn handle_hoc_request(state: &AppState, params: &HocParams) -> Result<HocR
 
     let cached = state.cache.load(params)?;
     Ok(
-        if let Some(CacheEntry::Cached {
+        if let Some(CacheEntry::Cached { details: None, .. }) = cached
+            && params.details
+        {
+            // counted again with details
+            HocResult::Loading
+        } else if let Some(CacheEntry::Cached {
             head,
-            count,
+            additions,
+            deletions,
             commits,
+            binary_changes,
+            details,
+            submodules,
+            ..
         }) = cached
         {
-            #[allow(clippy::cast_precision_loss)]
-            let hoc_pretty = match NumberPrefix::decimal(count as f64) {
-                NumberPrefix::Standalone(hoc) => hoc.to_string(),
-                NumberPrefix::Prefixed(prefix, hoc) => format!("{hoc:.1}{prefix}"),
+            let details = details.unwrap_or_default();
+            let (additions, deletions, commits, binary_changes) = if window.is_all() {
+                (additions, deletions, commits, binary_changes)
+            } else {
+                window.count(&details.timeline)
             };
             HocResult::Hoc {
-                hoc: count,
+                additions,
+                deletions,
                 commits,
-                hoc_pretty,
+                binary_changes,
                 head,
-                params: params.clone(),
+                details,
+                submodules,
+                params: Box::new(params.clone()),
+            }
+        } else if let Some(CacheEntry::Outdated {
+            ref head,
+            count,
+            commits,
+        }) = cached
+            && !params.details
+        {
+            HocResult::Outdated {
+                count,
+                commits,
+                head: head.clone(),
             }
         } else if matches!(cached, Some(CacheEntry::NotFound)) {
             HocResult::NotFound
+        } else if matches!(cached, Some(CacheEntry::Unauthorized)) {
+            HocResult::Unauthorized
         } else {
             HocResult::Loading
         },
     )
 }
 
-pub(crate) async fn json_hoc(
-    State(state): State<Arc<AppState>>,
-    ReqPath((platform, owner, repo)): ReqPath<(Platform, String, String)>,
-    Query(query): Query<BadgeQuery>,
-) -> Result<impl IntoResponse> {
-    let exclude = query.excludes();
-    let params = HocParams::new(platform, owner, repo, query.branch.clone(), exclude);
-    let r = handle_hoc_request(&state, &params).await?;
+async fn json_hoc(state: &AppState, id: RepoId, query: &BadgeQuery) -> Result<impl IntoResponse> {
+    let params = HocParams::new(id, query.branch(), query.options(state.settings.diff)?);
+    let r = handle_hoc_request(state, &params, &query.window()?).await?;
     Ok(match r {
         HocResult::NotFound => Json(json!({
             "status": "repo not found",
         }))
         .into_response(),
+        HocResult::Unauthorized => Json(json!({
+            "status": "unauthorized",
+        }))
+        .into_response(),
         HocResult::Hoc {
-            hoc, head, commits, ..
+            additions,
+            deletions,
+            head,
+            commits,
+            binary_changes,
+            submodules,
+            ..
         } => Json(JsonResponse {
-            branch: query.branch.as_deref().unwrap_or("default branch"),
+            branch: query.branch_name(),
             head: &head,
-            count: hoc,
+            count: additions.saturating_add(deletions),
+            additions,
+            deletions,
             commits,
+            binary_changes: (params.options.diff.binary == Binary::Report)
+                .then_some(binary_changes),
+            submodules: params
+                .options
+                .recurse_submodules
+                .then(|| submodules.iter().map(SubmoduleResponse::from).collect()),
         })
         .into_response(),
+        HocResult::Outdated {
+            count,
+            commits,
+            head,
+        } => Json(json!({
+            "head": head,
+            "branch": query.branch_name(),
+            "count": count,
+            "commits": commits,
+        }))
+        .into_response(),
         HocResult::Loading => Json(json!({
             "status": "loading",
         }))
@@ -188,6 +805,208 @@ pub(crate) async fn json_hoc(
     })
 }
 
+async fn json_authors(
+    state: &AppState,
+    id: RepoId,
+    query: &BadgeQuery,
+) -> Result<impl IntoResponse> {
+    let params =
+        HocParams::new(id, query.branch(), query.options(state.settings.diff)?).with_details();
+    let r = handle_hoc_request(state, &params, &Window::default()).await?;
+    Ok(match r {
+        HocResult::NotFound => Json(json!({
+            "status": "repo not found",
+        }))
+        .into_response(),
+        HocResult::Unauthorized => Json(json!({
+            "status": "unauthorized",
+        }))
+        .into_response(),
+        HocResult::Hoc { head, details, .. } => Json(AuthorsResponse {
+            branch: query.branch_name(),
+            head: &head,
+            authors: details.authors.iter().map(AuthorResponse::from).collect(),
+        })
+        .into_response(),
+        HocResult::Outdated { .. } | HocResult::Loading => Json(json!({
+            "status": "loading",
+        }))
+        .into_response(),
+    })
+}
+
+/// Sum up the lines of `files` by their first `depth` directories.
+///
+/// Files with less than `depth` directories are grouped by their parent directory, files in the
+/// root are grouped as `.`.
+fn directories(files: &BTreeMap<String, Lines>, depth: usize) -> Vec<DirectoryResponse> {
+    let mut directories: BTreeMap<String, Lines> = BTreeMap::new();
+    for (path, lines) in files {
+        let mut segments: Vec<_> = path.split('/').collect();
+        // drop the file name
+        segments.pop();
+        segments.truncate(depth);
+        let directory = if segments.is_empty() {
+            ".".to_string()
+        } else {
+            segments.join("/")
+        };
+        directories.entry(directory).or_default().add(*lines);
+    }
+    let mut directories: Vec<_> = directories
+        .into_iter()
+        .map(|(path, lines)| DirectoryResponse {
+            path,
+            count: lines.additions.saturating_add(lines.deletions),
+            additions: lines.additions,
+            deletions: lines.deletions,
+        })
+        .collect();
+    directories.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.path.cmp(&b.path)));
+    directories
+}
+
+async fn json_tree(state: &AppState, id: RepoId, query: &BadgeQuery) -> Result<impl IntoResponse> {
+    let params =
+        HocParams::new(id, query.branch(), query.options(state.settings.diff)?).with_details();
+    let depth = query.depth.unwrap_or(DEFAULT_TREE_DEPTH);
+    let r = handle_hoc_request(state, &params, &Window::default()).await?;
+    Ok(match r {
+        HocResult::NotFound => Json(json!({
+            "status": "repo not found",
+        }))
+        .into_response(),
+        HocResult::Unauthorized => Json(json!({
+            "status": "unauthorized",
+        }))
+        .into_response(),
+        HocResult::Hoc { head, details, .. } => Json(TreeResponse {
+            branch: query.branch_name(),
+            head: &head,
+            depth,
+            directories: directories(&details.files, depth),
+        })
+        .into_response(),
+        HocResult::Outdated { .. } | HocResult::Loading => Json(json!({
+            "status": "loading",
+        }))
+        .into_response(),
+    })
+}
+
+async fn json_languages(
+    state: &AppState,
+    id: RepoId,
+    query: &BadgeQuery,
+) -> Result<impl IntoResponse> {
+    let params =
+        HocParams::new(id, query.branch(), query.options(state.settings.diff)?).with_details();
+    let r = handle_hoc_request(state, &params, &Window::default()).await?;
+    Ok(match r {
+        HocResult::NotFound => Json(json!({
+            "status": "repo not found",
+        }))
+        .into_response(),
+        HocResult::Unauthorized => Json(json!({
+            "status": "unauthorized",
+        }))
+        .into_response(),
+        HocResult::Hoc { head, details, .. } => Json(LanguagesResponse {
+            branch: query.branch_name(),
+            head: &head,
+            languages: language::breakdown(&details.files)
+                .into_iter()
+                .map(|(language, lines)| LanguageResponse {
+                    name: language.name,
+                    count: lines.additions.saturating_add(lines.deletions),
+                    additions: lines.additions,
+                    deletions: lines.deletions,
+                })
+                .collect(),
+        })
+        .into_response(),
+        HocResult::Outdated { .. } | HocResult::Loading => Json(json!({
+            "status": "loading",
+        }))
+        .into_response(),
+    })
+}
+
+async fn history(state: &AppState, id: RepoId, query: &BadgeQuery) -> Result<impl IntoResponse> {
+    let params =
+        HocParams::new(id, query.branch(), query.options(state.settings.diff)?).with_details();
+    let window = query.window()?;
+    let r = handle_hoc_request(state, &params, &window).await?;
+    Ok(match r {
+        HocResult::NotFound => Json(json!({
+            "status": "repo not found",
+        }))
+        .into_response(),
+        HocResult::Unauthorized => Json(json!({
+            "status": "unauthorized",
+        }))
+        .into_response(),
+        HocResult::Hoc { head, details, .. } => {
+            let bucket = query.bucket.unwrap_or_default();
+            let points = history::series(&details.timeline, &window, bucket);
+            match query.format.unwrap_or_default() {
+                Format::Json => Json(HistoryResponse {
+                    branch: query.branch_name(),
+                    head: &head,
+                    bucket: bucket.as_str(),
+                    history: points,
+                })
+                .into_response(),
+                Format::Csv => (
+                    [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+                    history::csv(&points),
+                )
+                    .into_response(),
+            }
+        }
+        HocResult::Outdated { .. } | HocResult::Loading => Json(json!({
+            "status": "loading",
+        }))
+        .into_response(),
+    })
+}
+
+/// Share of each language in the HoC, for the bar on the overview page.
+fn language_shares(files: &BTreeMap<String, Lines>) -> Vec<LanguageShare> {
+    let languages = language::breakdown(files);
+    let total: u64 = languages
+        .iter()
+        .map(|(_, lines)| lines.additions.saturating_add(lines.deletions))
+        .fold(0, u64::saturating_add);
+    languages
+        .into_iter()
+        .map(|(language, lines)| {
+            let hoc = lines.additions.saturating_add(lines.deletions);
+            #[allow(clippy::cast_precision_loss)]
+            let percent = if total == 0 {
+                0.0
+            } else {
+                hoc as f64 * 100.0 / total as f64
+            };
+            LanguageShare {
+                name: language.name,
+                color: language.color,
+                hoc,
+                percent: format!("{percent:.1}"),
+            }
+        })
+        .collect()
+}
+
+/// Format a number with a decimal prefix, e.g. `1.2k`.
+fn pretty(value: u64) -> String {
+    #[allow(clippy::cast_precision_loss)]
+    match NumberPrefix::decimal(value as f64) {
+        NumberPrefix::Standalone(value) => value.to_string(),
+        NumberPrefix::Prefixed(prefix, value) => format!("{value:.1}{prefix}"),
+    }
+}
+
 fn no_cache_headers(expires: &Timestamp) -> HeaderMap {
     const FORMATTER: rfc2822::DateTimePrinter = rfc2822::DateTimePrinter::new();
     let mut headers = HeaderMap::new();
@@ -227,29 +1046,52 @@ fn no_cache_response(body: Vec<u8>) -> impl IntoResponse {
     (StatusCode::OK, no_cache_headers(&expiration), body)
 }
 
-pub(crate) async fn calculate_hoc(
-    State(state): State<Arc<AppState>>,
-    ReqPath((platform, owner, repo)): ReqPath<(Platform, String, String)>,
-    Query(query): Query<BadgeQuery>,
+async fn calculate_hoc(
+    state: &AppState,
+    id: RepoId,
+    query: &BadgeQuery,
 ) -> Result<impl IntoResponse> {
-    let exclude = query.excludes();
-    let params = HocParams::new(platform, owner, repo, query.branch.clone(), exclude);
-    let badge_opt = if let Ok(r) = handle_hoc_request(&state, &params).await {
+    let result = match (query.options(state.settings.diff), query.window()) {
+        (Ok(options), Ok(window)) => {
+            let params = HocParams::new(id, query.branch(), options);
+            handle_hoc_request(state, &params, &window).await
+        }
+        (Err(err), _) | (_, Err(err)) => Err(err),
+    };
+    let badge_opt = if let Ok(r) = result {
         match r {
             HocResult::NotFound => BadgeOptions {
                 subject: query.label().to_string(),
                 status: "repo not found".to_string(),
                 color: "#ff0000".to_string(),
             },
-            HocResult::Loading => BadgeOptions {
+            HocResult::Unauthorized => BadgeOptions {
+                subject: query.label().to_string(),
+                status: "unauthorized".to_string(),
+                color: "#fe7d37".to_string(),
+            },
+            HocResult::Outdated { count, .. } if query.metric() == Metric::Hoc => BadgeOptions {
+                subject: query.label().to_string(),
+                color: "#007ec6".to_string(),
+                status: pretty(count),
+            },
+            HocResult::Outdated { .. } | HocResult::Loading => BadgeOptions {
                 subject: query.label().to_string(),
                 status: "loading".to_string(),
                 color: "#ffff00".to_string(),
             },
-            HocResult::Hoc { hoc_pretty, .. } => BadgeOptions {
+            HocResult::Hoc {
+                additions,
+                deletions,
+                ..
+            } => BadgeOptions {
                 subject: query.label().to_string(),
                 color: "#007ec6".to_string(),
-                status: hoc_pretty,
+                status: pretty(match query.metric() {
+                    Metric::Hoc => additions.saturating_add(deletions),
+                    Metric::Additions => additions,
+                    Metric::Deletions => deletions,
+                }),
             },
         }
     } else {
@@ -266,18 +1108,15 @@ pub(crate) async fn calculate_hoc(
     Ok(no_cache_response(body))
 }
 
-pub(crate) async fn overview(
-    State(state): State<Arc<AppState>>,
-    ReqPath((platform, owner, repo)): ReqPath<(Platform, String, String)>,
-    Query(query): Query<BadgeQuery>,
-) -> Result<impl IntoResponse> {
+async fn overview(state: &AppState, id: RepoId, query: &BadgeQuery) -> Result<impl IntoResponse> {
     let base_url = state.settings.base_url.clone();
-    let exclude = query.excludes();
-    let params = HocParams::new(platform, owner, repo, query.branch.clone(), exclude);
-    let r = handle_hoc_request(&state, &params).await?;
+    let params =
+        HocParams::new(id, query.branch(), query.options(state.settings.diff)?).with_details();
+    let r = handle_hoc_request(state, &params, &query.window()?).await?;
     match r {
-        HocResult::NotFound => Ok(repo_not_found(&state).into_response()),
-        HocResult::Loading => {
+        HocResult::NotFound => Ok(repo_not_found(state).into_response()),
+        HocResult::Unauthorized => Ok(unauthorized(state).into_response()),
+        HocResult::Outdated { .. } | HocResult::Loading => {
             let repo_info = RepoInfo {
                 commit_url: "",
                 commits: 0,
@@ -285,9 +1124,14 @@ pub(crate) async fn overview(
                 head: "",
                 hoc: 0,
                 hoc_pretty: "",
+                additions: 0,
+                deletions: 0,
+                authors: &[],
+                author_count: 0,
+                languages: &[],
                 path: &params.service_path(),
-                url: &params.url(),
-                branch: query.branch.as_deref().unwrap_or("default branch"),
+                url: &params.web_url(),
+                branch: query.branch_name(),
                 query: &query.to_query(),
             };
             let label = query.label();
@@ -301,22 +1145,35 @@ pub(crate) async fn overview(
             .into_response())
         }
         HocResult::Hoc {
-            hoc,
+            additions,
+            deletions,
             commits,
-            hoc_pretty,
             head,
+            details,
             params,
+            ..
         } => {
+            let Details { authors, files, .. } = &*details;
+            let hoc = additions.saturating_add(deletions);
             let repo_info = RepoInfo {
-                commit_url: &platform.commit_url(&params.owner, &params.repo, &head),
+                commit_url: &params.id.platform().commit_url(
+                    params.id.owner(),
+                    params.id.repo(),
+                    &head,
+                ),
                 commits,
                 base_url: &base_url,
                 head: &head,
                 hoc,
-                hoc_pretty: &hoc_pretty,
+                hoc_pretty: &pretty(hoc),
+                additions,
+                deletions,
+                authors: &authors[..authors.len().min(OVERVIEW_AUTHORS)],
+                author_count: authors.len(),
+                languages: &language_shares(files),
                 path: &params.service_path(),
-                url: &params.url(),
-                branch: query.branch.as_deref().unwrap_or("default branch"),
+                url: &params.web_url(),
+                branch: query.branch_name(),
                 query: &query.to_query(),
             };
             let label = query.label();
@@ -332,6 +1189,17 @@ pub(crate) async fn overview(
     }
 }
 
+fn unauthorized(state: &AppState) -> impl IntoResponse {
+    (
+        StatusCode::FORBIDDEN,
+        render!(
+            templates::p403_unauthorized_html,
+            VERSION_INFO,
+            state.repo_count.load(Ordering::Relaxed)
+        ),
+    )
+}
+
 fn repo_not_found(state: &AppState) -> impl IntoResponse {
     (
         StatusCode::NOT_FOUND,
@@ -342,3 +1210,107 @@ fn repo_not_found(state: &AppState) -> impl IntoResponse {
         ),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, DirectoryResponse, directories, split_repo_path};
+    use crate::{cache::Lines, platform::Platform};
+
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn split_simple_path() {
+        let (id, action) = split_repo_path(Platform::GitHub, "vbrandl/hoc").unwrap();
+        assert_eq!(id.owner(), "vbrandl");
+        assert_eq!(id.repo(), "hoc");
+        assert_eq!(action, Action::Badge);
+    }
+
+    #[test]
+    fn split_nested_path() {
+        let (id, action) =
+            split_repo_path(Platform::Gitlab, "group/subgroup/project/view").unwrap();
+        assert_eq!(id.owner(), "group/subgroup");
+        assert_eq!(id.repo(), "project");
+        assert_eq!(action, Action::View);
+    }
+
+    #[test]
+    fn split_authors_path() {
+        let (id, action) = split_repo_path(Platform::GitHub, "vbrandl/hoc/authors").unwrap();
+        assert_eq!(id.owner(), "vbrandl");
+        assert_eq!(id.repo(), "hoc");
+        assert_eq!(action, Action::Authors);
+    }
+
+    #[test]
+    fn split_history_path() {
+        let (id, action) = split_repo_path(Platform::GitHub, "vbrandl/hoc/history").unwrap();
+        assert_eq!(id.repo(), "hoc");
+        assert_eq!(action, Action::History);
+    }
+
+    #[test]
+    fn split_repo_named_like_action() {
+        let (id, action) = split_repo_path(Platform::GitHub, "vbrandl/json").unwrap();
+        assert_eq!(id.owner(), "vbrandl");
+        assert_eq!(id.repo(), "json");
+        assert_eq!(action, Action::Badge);
+    }
+
+    #[test]
+    fn split_relative_path() {
+        assert!(split_repo_path(Platform::GitHub, "../../etc").is_err());
+        assert!(split_repo_path(Platform::GitHub, "vbrandl/../hoc").is_err());
+    }
+
+    #[test]
+    fn split_missing_repo() {
+        assert!(split_repo_path(Platform::GitHub, "vbrandl").is_err());
+        assert!(split_repo_path(Platform::GitHub, "").is_err());
+    }
+
+    fn lines(additions: u64, deletions: u64) -> Lines {
+        Lines {
+            additions,
+            deletions,
+        }
+    }
+
+    #[test]
+    fn directories_by_depth() {
+        let files = BTreeMap::from([
+            ("README.md".to_string(), lines(1, 0)),
+            ("crates/a/src/lib.rs".to_string(), lines(5, 2)),
+            ("crates/a/Cargo.toml".to_string(), lines(1, 1)),
+            ("crates/b/src/lib.rs".to_string(), lines(3, 0)),
+        ]);
+
+        let dirs = directories(&files, 1);
+        assert_eq!(
+            dirs,
+            vec![
+                DirectoryResponse {
+                    path: "crates".to_string(),
+                    count: 12,
+                    additions: 9,
+                    deletions: 3,
+                },
+                DirectoryResponse {
+                    path: ".".to_string(),
+                    count: 1,
+                    additions: 1,
+                    deletions: 0,
+                },
+            ]
+        );
+
+        let dirs = directories(&files, 2);
+        let paths: Vec<_> = dirs.iter().map(|d| (d.path.as_str(), d.count)).collect();
+        assert_eq!(paths, vec![("crates/a", 9), ("crates/b", 3), (".", 1)]);
+
+        let dirs = directories(&files, 0);
+        let paths: Vec<_> = dirs.iter().map(|d| (d.path.as_str(), d.count)).collect();
+        assert_eq!(paths, vec![(".", 13)]);
+    }
+}
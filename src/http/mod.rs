@@ -8,13 +8,15 @@ use crate::{
     cache::{HocParams, Persist},
     config::Settings,
     error::Error,
-    platform::Platform,
     statics::VERSION_INFO,
     templates,
     worker::{Queue, worker},
 };
 
-use std::sync::{Arc, atomic::AtomicUsize};
+use std::{
+    sync::{Arc, atomic::AtomicUsize},
+    time::Duration,
+};
 
 use axum::{
     Router,
@@ -36,6 +38,7 @@ pub struct AppState {
     pub repo_count: AtomicUsize,
     pub cache: Persist,
     pub queue: Queue<HocParams>,
+    pub client: reqwest::Client,
 }
 
 impl AppState {
@@ -44,17 +47,31 @@ impl AppState {
     }
 }
 
+/// HTTP client used to talk to forges.
+///
+/// # Errors
+///
+/// * the TLS backend cannot be initialized
+pub fn client() -> Result<reqwest::Client, Error> {
+    Ok(reqwest::Client::builder()
+        // some forges only serve the smart HTTP endpoints to git clients
+        .user_agent(concat!("git/hoc-", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(30))
+        .build()?)
+}
+
 #[instrument(skip(state))]
 async fn redirect_old_overview(
     State(state): State<Arc<AppState>>,
-    Path((platform, owner, repo)): Path<(Platform, String, String)>,
-) -> impl IntoResponse {
+    Path((platform, owner, repo)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse, Error> {
     warn!("request to deprecated endpoint");
-    Redirect::permanent(&format!(
+    let platform = state.settings.platform(&platform)?;
+    Ok(Redirect::permanent(&format!(
         "{}/{}/{owner}/{repo}/view",
         state.settings.base_url,
         platform.url_path()
-    ))
+    )))
 }
 
 pub fn router(state: Arc<AppState>) -> Router {
@@ -72,13 +89,18 @@ pub fn router(state: Arc<AppState>) -> Router {
         .route("/generate", get(routes::generate))
         .route("/static/{filename}", get(routes::static_file))
         .route("/view/{platform}/{user}/{repo}", get(redirect_old_overview))
-        .nest(
-            "/{platform}/{user}/{repo}",
-            Router::new()
-                .route("/", get(hoc::calculate_hoc))
-                .route("/json", get(hoc::json_hoc))
-                .route("/view", get(hoc::overview))
-                .route("/delete", post(hoc::delete_repo_and_cache)),
+        .route("/git", get(hoc::git_badge))
+        .route("/git/badge", get(hoc::git_badge))
+        .route("/git/json", get(hoc::git_json))
+        .route("/git/authors", get(hoc::git_authors))
+        .route("/git/tree", get(hoc::git_tree))
+        .route("/git/languages", get(hoc::git_languages))
+        .route("/git/history", get(hoc::git_history))
+        .route("/git/view", get(hoc::git_overview))
+        .route("/git/delete", post(hoc::git_delete))
+        .route(
+            "/{platform}/{*path}",
+            get(hoc::get_repo).post(hoc::post_repo),
         )
         .fallback(routes::p404)
         .layer(
@@ -109,12 +131,32 @@ pub fn router(state: Arc<AppState>) -> Router {
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        if matches!(self, Self::BranchNotFound) || matches!(self, Self::UnknownPlatform(_)) {
+        if matches!(self, Self::BranchNotFound) {
             (
                 StatusCode::NOT_FOUND,
                 render!(templates::p404_no_master_html, VERSION_INFO, 0),
             )
                 .into_response()
+        } else if let Self::InvalidRevision(revision) = self {
+            Self::InvalidQuery(format!("`{revision}` is not a branch, tag or commit hash"))
+                .into_response()
+        } else if let Self::InvalidQuery(message) = self {
+            (
+                StatusCode::BAD_REQUEST,
+                render!(
+                    templates::p400_invalid_query_html,
+                    VERSION_INFO,
+                    0,
+                    &message
+                ),
+            )
+                .into_response()
+        } else if matches!(self, Self::UnknownPlatform(_) | Self::InvalidRepo(_)) {
+            (
+                StatusCode::NOT_FOUND,
+                render!(templates::p404_html, VERSION_INFO, 0),
+            )
+                .into_response()
         } else {
             error!(err=%self, "error");
             (
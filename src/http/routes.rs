@@ -1,6 +1,6 @@
 use crate::{
+    error::Result,
     http::AppState,
-    platform::Platform,
     statics::VERSION_INFO,
     template::RepoGeneratorInfo,
     templates::{self, statics::StaticFile},
@@ -29,25 +29,24 @@ pub(crate) async fn index(State(state): State<Arc<AppState>>) -> impl IntoRespon
         VERSION_INFO,
         state.repo_count.load(Ordering::Relaxed),
         &state.settings.base_url,
+        &state.settings.platforms,
     )
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub(crate) struct GeneratorForm<'a> {
-    service: Platform,
+    service: Cow<'a, str>,
     user: Cow<'a, str>,
     repo: Cow<'a, str>,
     branch: Option<Cow<'a, str>>,
     exclude: Option<Cow<'a, str>>,
+    include: Option<Cow<'a, str>>,
 }
 
-#[instrument(skip(state))]
-pub(crate) async fn generate(
-    State(state): State<Arc<AppState>>,
-    Query(form): Query<GeneratorForm<'_>>,
-) -> impl IntoResponse {
-    let exclude = form.exclude.map(|e| {
-        e.split(&[',', '\n'])
+/// Turn patterns separated by commas or newlines into a sorted, comma separated list.
+fn join_patterns(patterns: Option<&str>) -> Option<String> {
+    patterns.map(|p| {
+        p.split(&[',', '\n'])
             .map(str::trim)
             // remove duplicates and sort
             .collect::<BTreeSet<_>>()
@@ -55,20 +54,31 @@ pub(crate) async fn generate(
             // is there a better way to join?
             .collect::<Vec<_>>()
             .join(",")
-    });
-    render!(
+    })
+}
+
+#[instrument(skip(state))]
+pub(crate) async fn generate(
+    State(state): State<Arc<AppState>>,
+    Query(form): Query<GeneratorForm<'_>>,
+) -> Result<impl IntoResponse> {
+    let platform = state.settings.platform(&form.service)?;
+    let exclude = join_patterns(form.exclude.as_deref());
+    let include = join_patterns(form.include.as_deref().filter(|s| !s.trim().is_empty()));
+    Ok(render!(
         templates::generate_html,
         VERSION_INFO,
         state.repo_count.load(Ordering::Relaxed),
         &state.settings.base_url,
         &RepoGeneratorInfo {
-            platform: form.service,
+            platform,
             user: &form.user,
             repo: &form.repo,
             branch: form.branch.as_deref().filter(|s| !s.is_empty()),
             exclude: exclude.as_deref(),
+            include: include.as_deref(),
         }
-    )
+    ))
 }
 
 pub(crate) async fn static_file(
@@ -0,0 +1,400 @@
+use crate::cache::Lines;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::LazyLock,
+};
+
+/// A programming, markup or data language, identified by file extensions and names.
+///
+/// Names and colors follow GitHub's linguist.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Language {
+    pub name: &'static str,
+    /// Color used on the overview page
+    pub color: &'static str,
+    /// Lowercase file extensions without the leading dot
+    extensions: &'static [&'static str],
+    /// Exact file names, e.g. `Makefile`
+    filenames: &'static [&'static str],
+}
+
+/// Files that match no other language.
+pub(crate) const OTHER: Language = Language {
+    name: "Other",
+    color: "#cccccc",
+    extensions: &[],
+    filenames: &[],
+};
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        name: "C",
+        color: "#555555",
+        extensions: &["c", "h"],
+        filenames: &[],
+    },
+    Language {
+        name: "C#",
+        color: "#178600",
+        extensions: &["cs", "csx"],
+        filenames: &[],
+    },
+    Language {
+        name: "C++",
+        color: "#f34b7d",
+        extensions: &["cc", "cpp", "cxx", "c++", "hh", "hpp", "hxx", "h++", "ipp"],
+        filenames: &[],
+    },
+    Language {
+        name: "CMake",
+        color: "#da3434",
+        extensions: &["cmake"],
+        filenames: &["CMakeLists.txt"],
+    },
+    Language {
+        name: "CSS",
+        color: "#663399",
+        extensions: &["css"],
+        filenames: &[],
+    },
+    Language {
+        name: "Clojure",
+        color: "#db5855",
+        extensions: &["clj", "cljs", "cljc", "edn"],
+        filenames: &[],
+    },
+    Language {
+        name: "Dart",
+        color: "#00b4ab",
+        extensions: &["dart"],
+        filenames: &[],
+    },
+    Language {
+        name: "Dockerfile",
+        color: "#384d54",
+        extensions: &["dockerfile"],
+        filenames: &["Dockerfile", "Containerfile"],
+    },
+    Language {
+        name: "Elixir",
+        color: "#6e4a7e",
+        extensions: &["ex", "exs"],
+        filenames: &[],
+    },
+    Language {
+        name: "Elm",
+        color: "#60b5cc",
+        extensions: &["elm"],
+        filenames: &[],
+    },
+    Language {
+        name: "Erlang",
+        color: "#b83998",
+        extensions: &["erl", "hrl"],
+        filenames: &[],
+    },
+    Language {
+        name: "F#",
+        color: "#b845fc",
+        extensions: &["fs", "fsi", "fsx"],
+        filenames: &[],
+    },
+    Language {
+        name: "Go",
+        color: "#00add8",
+        extensions: &["go"],
+        filenames: &[],
+    },
+    Language {
+        name: "Haskell",
+        color: "#5e5086",
+        extensions: &["hs", "lhs"],
+        filenames: &[],
+    },
+    Language {
+        name: "HTML",
+        color: "#e34c26",
+        extensions: &["html", "htm", "xhtml"],
+        filenames: &[],
+    },
+    Language {
+        name: "Java",
+        color: "#b07219",
+        extensions: &["java"],
+        filenames: &[],
+    },
+    Language {
+        name: "JavaScript",
+        color: "#f1e05a",
+        extensions: &["js", "mjs", "cjs", "jsx"],
+        filenames: &[],
+    },
+    Language {
+        name: "JSON",
+        color: "#292929",
+        extensions: &["json"],
+        filenames: &[],
+    },
+    Language {
+        name: "Julia",
+        color: "#a270ba",
+        extensions: &["jl"],
+        filenames: &[],
+    },
+    Language {
+        name: "Kotlin",
+        color: "#a97bff",
+        extensions: &["kt", "kts"],
+        filenames: &[],
+    },
+    Language {
+        name: "Lua",
+        color: "#000080",
+        extensions: &["lua"],
+        filenames: &[],
+    },
+    Language {
+        name: "Makefile",
+        color: "#427819",
+        extensions: &["mk", "mak"],
+        filenames: &["Makefile", "makefile", "GNUmakefile"],
+    },
+    Language {
+        name: "Markdown",
+        color: "#083fa1",
+        extensions: &["md", "markdown"],
+        filenames: &[],
+    },
+    Language {
+        name: "Nix",
+        color: "#7e7eff",
+        extensions: &["nix"],
+        filenames: &[],
+    },
+    Language {
+        name: "OCaml",
+        color: "#ef7a08",
+        extensions: &["ml", "mli"],
+        filenames: &[],
+    },
+    Language {
+        name: "Objective-C",
+        color: "#438eff",
+        extensions: &["m", "mm"],
+        filenames: &[],
+    },
+    Language {
+        name: "PHP",
+        color: "#4f5d95",
+        extensions: &["php"],
+        filenames: &[],
+    },
+    Language {
+        name: "Perl",
+        color: "#0298c3",
+        extensions: &["pl", "pm"],
+        filenames: &[],
+    },
+    Language {
+        name: "Python",
+        color: "#3572a5",
+        extensions: &["py", "pyi"],
+        filenames: &[],
+    },
+    Language {
+        name: "R",
+        color: "#198ce7",
+        extensions: &["r"],
+        filenames: &[],
+    },
+    Language {
+        name: "Ruby",
+        color: "#701516",
+        extensions: &["rb", "rake", "gemspec"],
+        filenames: &["Gemfile", "Rakefile"],
+    },
+    Language {
+        name: "Rust",
+        color: "#dea584",
+        extensions: &["rs"],
+        filenames: &[],
+    },
+    Language {
+        name: "SCSS",
+        color: "#c6538c",
+        extensions: &["scss", "sass"],
+        filenames: &[],
+    },
+    Language {
+        name: "SQL",
+        color: "#e38c00",
+        extensions: &["sql"],
+        filenames: &[],
+    },
+    Language {
+        name: "Scala",
+        color: "#c22d40",
+        extensions: &["scala", "sc"],
+        filenames: &[],
+    },
+    Language {
+        name: "Shell",
+        color: "#89e051",
+        extensions: &["sh", "bash", "zsh", "fish"],
+        filenames: &[],
+    },
+    Language {
+        name: "Swift",
+        color: "#f05138",
+        extensions: &["swift"],
+        filenames: &[],
+    },
+    Language {
+        name: "TOML",
+        color: "#9c4221",
+        extensions: &["toml"],
+        filenames: &["Cargo.lock"],
+    },
+    Language {
+        name: "TypeScript",
+        color: "#3178c6",
+        extensions: &["ts", "tsx", "mts", "cts"],
+        filenames: &[],
+    },
+    Language {
+        name: "Vue",
+        color: "#41b883",
+        extensions: &["vue"],
+        filenames: &[],
+    },
+    Language {
+        name: "XML",
+        color: "#0060ac",
+        extensions: &["xml", "xsd", "xsl", "svg"],
+        filenames: &[],
+    },
+    Language {
+        name: "YAML",
+        color: "#cb171e",
+        extensions: &["yml", "yaml"],
+        filenames: &[],
+    },
+    Language {
+        name: "Zig",
+        color: "#ec915c",
+        extensions: &["zig"],
+        filenames: &[],
+    },
+];
+
+static BY_EXTENSION: LazyLock<HashMap<&'static str, &'static Language>> = LazyLock::new(|| {
+    LANGUAGES
+        .iter()
+        .flat_map(|language| language.extensions.iter().map(move |ext| (*ext, language)))
+        .collect()
+});
+
+static BY_FILENAME: LazyLock<HashMap<&'static str, &'static Language>> = LazyLock::new(|| {
+    LANGUAGES
+        .iter()
+        .flat_map(|language| language.filenames.iter().map(move |name| (*name, language)))
+        .collect()
+});
+
+/// Classify a file by its name or extension.
+///
+/// File names take precedence over extensions, so `CMakeLists.txt` is CMake and not text.
+pub(crate) fn classify(path: &str) -> &'static Language {
+    let filename = path.rsplit('/').next().unwrap_or(path);
+    if let Some(language) = BY_FILENAME.get(filename) {
+        return language;
+    }
+    filename
+        .rsplit_once('.')
+        // hidden files without extension, e.g. `.gitignore`
+        .filter(|(stem, _)| !stem.is_empty())
+        .and_then(|(_, ext)| BY_EXTENSION.get(ext.to_lowercase().as_str()).copied())
+        .unwrap_or(&OTHER)
+}
+
+/// Sum up the lines of `files` by language, sorted by HoC.
+pub(crate) fn breakdown(files: &BTreeMap<String, Lines>) -> Vec<(&'static Language, Lines)> {
+    let mut languages: HashMap<&'static str, (&'static Language, Lines)> = HashMap::new();
+    for (path, lines) in files {
+        let language = classify(path);
+        languages
+            .entry(language.name)
+            .or_insert((language, Lines::default()))
+            .1
+            .add(*lines);
+    }
+    let mut languages: Vec<_> = languages.into_values().collect();
+    languages.sort_by(|(a, a_lines), (b, b_lines)| {
+        (b_lines.additions.saturating_add(b_lines.deletions))
+            .cmp(&a_lines.additions.saturating_add(a_lines.deletions))
+            .then_with(|| a.name.cmp(b.name))
+    });
+    languages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OTHER, breakdown, classify};
+    use crate::cache::Lines;
+
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn classify_by_extension() {
+        assert_eq!(classify("src/main.rs").name, "Rust");
+        assert_eq!(classify("include/Foo.HPP").name, "C++");
+        assert_eq!(classify("README.md").name, "Markdown");
+    }
+
+    #[test]
+    fn classify_by_filename() {
+        assert_eq!(classify("CMakeLists.txt").name, "CMake");
+        assert_eq!(classify("docker/Dockerfile").name, "Dockerfile");
+        assert_eq!(classify("Makefile").name, "Makefile");
+    }
+
+    #[test]
+    fn classify_unknown() {
+        assert_eq!(classify("LICENSE"), &OTHER);
+        assert_eq!(classify(".gitignore"), &OTHER);
+        assert_eq!(classify("data.unknown"), &OTHER);
+    }
+
+    #[test]
+    fn breakdown_sorted_by_hoc() {
+        let files = BTreeMap::from([
+            (
+                "src/lib.rs".to_string(),
+                Lines {
+                    additions: 3,
+                    deletions: 1,
+                },
+            ),
+            (
+                "src/main.rs".to_string(),
+                Lines {
+                    additions: 2,
+                    deletions: 0,
+                },
+            ),
+            (
+                "README.md".to_string(),
+                Lines {
+                    additions: 10,
+                    deletions: 0,
+                },
+            ),
+        ]);
+        let languages: Vec<_> = breakdown(&files)
+            .into_iter()
+            .map(|(language, lines)| (language.name, lines.additions, lines.deletions))
+            .collect();
+        assert_eq!(languages, vec![("Markdown", 10, 0), ("Rust", 5, 1)]);
+    }
+}
@@ -1,13 +1,23 @@
+mod attributes;
 pub mod cache;
 pub mod config;
 pub mod count;
+mod diff;
 mod error;
+mod history;
 mod hoc;
 pub mod http;
+mod language;
 mod platform;
+mod rename;
+mod repo_id;
+mod ssh;
 mod statics;
+mod store;
+mod submodule;
 pub mod telemetry;
 mod template;
+mod window;
 pub mod worker;
 
 use std::sync::{Arc, atomic::AtomicUsize};
@@ -26,11 +36,13 @@ async fn start_server(listener: TcpListener, settings: Settings) -> Result<()> {
     let queue = Queue::new();
     let cache = Persist::new(settings.clone());
     let repo_count = AtomicUsize::new(count_repositories(&settings.repodir)?);
+    let client = http::client()?;
     let state = Arc::new(AppState {
         settings,
         repo_count,
         cache,
         queue,
+        client,
     });
     let router = http::router(state.clone());
     axum::serve(listener, router)
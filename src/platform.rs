@@ -1,43 +1,187 @@
 use crate::error::Error;
 
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr, sync::Arc};
 
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
-#[derive(Deserialize, Serialize, Clone, Copy, Hash, Eq, PartialEq, Debug)]
+#[derive(Clone, Hash, Eq, PartialEq, Debug)]
 pub enum Platform {
-    #[serde(rename = "github")]
     GitHub,
-    #[serde(rename = "gitlab")]
     Gitlab,
-    #[serde(rename = "bitbucket")]
     Bitbucket,
-    #[serde(rename = "sourcehut")]
     Sourcehut,
+    Codeberg,
+    Gitea,
+    /// A forge configured in the `[[platforms]]` section of the config file
+    Custom(Arc<Forge>),
+    /// An arbitrary git remote on an allowed host, requested via the `/git` endpoints
+    Git(Arc<str>),
+    /// A repository below one of the configured `local_roots`
+    Local,
+}
+
+/// A self-hosted forge as configured in `hoc.toml`.
+///
+/// The defaults match the URL layout of GitHub, GitLab, Gitea and Forgejo, so for most instances
+/// only `name` and `domain` have to be set.
+///
+/// The URL templates may contain the placeholders `{domain}`, `{owner}` and `{repo}`. The commit
+/// URL template may also contain `{commit}`.
+#[derive(Deserialize, Clone, Hash, Eq, PartialEq, Debug)]
+pub struct Forge {
+    /// Name used in the request path, e.g. `/<name>/<owner>/<repo>`
+    pub name: String,
+    /// Domain of the forge
+    pub domain: String,
+    /// Template for the URL used to clone a repository
+    #[serde(default = "Forge::default_clone_url")]
+    pub clone_url: String,
+    /// Template for the URL pointing to a single commit
+    #[serde(default = "Forge::default_commit_url")]
+    pub commit_url: String,
+    /// Template for the URL of the web interface of a repository
+    #[serde(default = "Forge::default_web_url")]
+    pub web_url: String,
+    /// Clone and fetch via SSH instead of `clone_url`
+    pub ssh: Option<Ssh>,
+    /// The forge treats owner and repository names case-insensitively, like GitHub or Gitea
+    #[serde(default)]
+    pub case_insensitive: bool,
+}
+
+/// SSH transport for a forge.
+#[derive(Deserialize, Clone, Hash, Eq, PartialEq, Debug)]
+pub struct Ssh {
+    /// User to connect as
+    #[serde(default = "Ssh::default_user")]
+    pub user: String,
+    /// Port of the SSH server
+    pub port: Option<u16>,
+    /// Path to the private key
+    pub key: Option<PathBuf>,
+    /// Path to a `known_hosts` file. If set, only host keys listed in this file are accepted
+    pub known_hosts: Option<PathBuf>,
+}
+
+impl Ssh {
+    fn default_user() -> String {
+        "git".to_string()
+    }
+
+    fn url(&self, domain: &str, owner: &str, repo: &str) -> String {
+        let user = &self.user;
+        match self.port {
+            Some(port) => format!("ssh://{user}@{domain}:{port}/{owner}/{repo}.git"),
+            None => format!("{user}@{domain}:{owner}/{repo}.git"),
+        }
+    }
+}
+
+impl Forge {
+    fn default_clone_url() -> String {
+        "https://{domain}/{owner}/{repo}".to_string()
+    }
+
+    fn default_commit_url() -> String {
+        "https://{domain}/{owner}/{repo}/commit/{commit}".to_string()
+    }
+
+    fn default_web_url() -> String {
+        "https://{domain}/{owner}/{repo}".to_string()
+    }
+
+    fn expand(&self, template: &str, owner: &str, repo: &str) -> String {
+        template
+            .replace("{domain}", &self.domain)
+            .replace("{owner}", owner)
+            .replace("{repo}", repo)
+    }
 }
 
 impl Platform {
-    pub(crate) fn domain(self) -> &'static str {
+    /// Built-in platform hosted at `domain`.
+    pub(crate) fn from_domain(domain: &str) -> Option<Self> {
+        [
+            Self::GitHub,
+            Self::Gitlab,
+            Self::Bitbucket,
+            Self::Sourcehut,
+            Self::Codeberg,
+            Self::Gitea,
+        ]
+        .into_iter()
+        .find(|platform| platform.domain().eq_ignore_ascii_case(domain))
+    }
+
+    pub(crate) fn domain(&self) -> &str {
         match self {
             Self::GitHub => "github.com",
             Self::Gitlab => "gitlab.com",
             Self::Bitbucket => "bitbucket.org",
             Self::Sourcehut => "git.sr.ht",
+            Self::Codeberg => "codeberg.org",
+            Self::Gitea => "gitea.com",
+            Self::Custom(forge) => &forge.domain,
+            Self::Git(host) => host,
+            Self::Local => "local",
         }
     }
 
-    pub(crate) fn url_path(self) -> &'static str {
+    pub(crate) fn url_path(&self) -> &str {
         match self {
             Self::GitHub => "github",
             Self::Gitlab => "gitlab",
             Self::Bitbucket => "bitbucket",
             Self::Sourcehut => "sourcehut",
+            Self::Codeberg => "codeberg",
+            Self::Gitea => "gitea",
+            Self::Custom(forge) => &forge.name,
+            Self::Git(_) => "git",
+            Self::Local => "local",
+        }
+    }
+
+    pub(crate) fn web_url(&self, owner: &str, repo: &str) -> String {
+        match self {
+            Self::Custom(forge) => forge.expand(&forge.web_url, owner, repo),
+            Self::Git(_) => self.clone_url(owner, repo),
+            Self::Local => String::new(),
+            _ => format!("https://{}/{owner}/{repo}", self.domain()),
+        }
+    }
+
+    pub(crate) fn clone_url(&self, owner: &str, repo: &str) -> String {
+        match self {
+            Self::Custom(forge) => forge.ssh.as_ref().map_or_else(
+                || forge.expand(&forge.clone_url, owner, repo),
+                |ssh| ssh.url(&forge.domain, owner, repo),
+            ),
+            _ => format!("https://{}/{owner}/{repo}", self.domain()),
+        }
+    }
+
+    /// Whether `Owner/Repo` and `owner/repo` name the same repository.
+    ///
+    /// Plain git remotes and local directories are case-sensitive, so only the built-in platforms
+    /// and forges that opted in fold the case.
+    pub(crate) fn case_insensitive(&self) -> bool {
+        match self {
+            Self::Custom(forge) => forge.case_insensitive,
+            Self::Git(_) | Self::Local => false,
+            _ => true,
+        }
+    }
+
+    pub(crate) fn ssh(&self) -> Option<&Ssh> {
+        match self {
+            Self::Custom(forge) => forge.ssh.as_ref(),
+            _ => None,
         }
     }
 
-    pub(crate) fn commit_url(self, owner: &str, repo: &str, commit_ref: &str) -> String {
+    pub(crate) fn commit_url(&self, owner: &str, repo: &str, commit_ref: &str) -> String {
         match self {
-            Self::GitHub | Self::Gitlab | Self::Sourcehut => {
+            Self::GitHub | Self::Gitlab | Self::Sourcehut | Self::Codeberg | Self::Gitea => {
                 format!(
                     "https://{}/{owner}/{repo}/commit/{commit_ref}",
                     self.domain()
@@ -49,6 +193,13 @@ impl Platform {
                     self.domain(),
                 )
             }
+            Self::Custom(forge) => forge
+                .expand(&forge.commit_url, owner, repo)
+                .replace("{commit}", commit_ref),
+            // there is no known web interface, so link to the repository instead
+            Self::Git(_) => self.clone_url(owner, repo),
+            // local repositories are not accessible from the outside
+            Self::Local => String::new(),
         }
     }
 }
@@ -62,6 +213,8 @@ impl FromStr for Platform {
             "gitlab" => Ok(Self::Gitlab),
             "bitbucket" => Ok(Self::Bitbucket),
             "sourcehut" => Ok(Self::Sourcehut),
+            "codeberg" => Ok(Self::Codeberg),
+            "gitea" => Ok(Self::Gitea),
             _ => Err(Error::UnknownPlatform(s.to_string())),
         }
     }
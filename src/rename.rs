@@ -0,0 +1,386 @@
+use crate::error::Result;
+
+use std::collections::{HashMap, HashSet};
+
+use git2::{Delta, Diff, Odb, Oid, Repository, Tree, TreeWalkMode, TreeWalkResult};
+
+/// Score of identical files, like `MAX_SCORE` in git.
+const MAX_SCORE: u64 = 60000;
+/// Minimum score of a rename or copy, the default 50% of `-M` and `-C`.
+const MIN_SCORE: u64 = MAX_SCORE / 2;
+/// Default of `diff.renameLimit`.
+const RENAME_LIMIT: u64 = 1000;
+/// Number of sources that are remembered for each added file.
+const CANDIDATES: usize = 4;
+/// Modulus of the span hashes, like `HASHBASE` in git.
+const HASH_BASE: u32 = 107_927;
+/// Number of bytes git looks at to detect binary files.
+const FIRST_FEW_BYTES: usize = 8000;
+
+const MODE_REGULAR: i32 = 0o100_000;
+const MODE_TYPE: i32 = 0o170_000;
+
+/// A file in the old tree that added files may be paired with.
+struct Source {
+    path: Vec<u8>,
+    oid: Oid,
+    mode: i32,
+    /// Whether the file still exists in the new tree
+    stays: bool,
+    /// Number of added files paired with this file, plus one if it stays, like `rename_used`
+    used: usize,
+}
+
+/// An added file.
+struct Destination<'a> {
+    path: &'a [u8],
+    oid: Oid,
+    mode: i32,
+    source: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+struct Candidate {
+    destination: usize,
+    source: usize,
+    score: u64,
+    same_name: bool,
+}
+
+/// Added files paired with the file they were renamed or copied from.
+#[derive(Default)]
+pub(crate) struct Pairs {
+    /// Blob of the source of copied files
+    copies: HashMap<Vec<u8>, Oid>,
+    /// Added files that are renames
+    renamed: HashSet<Vec<u8>>,
+    /// Deleted files that were renamed
+    moved: HashSet<Vec<u8>>,
+}
+
+impl Pairs {
+    /// Pair the added files of `diff` with their sources in `old`, like
+    /// `git diff -M --find-copies-harder` does.
+    ///
+    /// Identical files are paired first. The remaining files are paired by git's similarity
+    /// estimate, which counts the bytes of the lines both files share, so the same files are
+    /// paired as in `git log`. Every file of `old` can be a source, unless there are more than
+    /// `diff.renameLimit` squared combinations. Then only modified and deleted files are
+    /// considered and if there are still too many, only identical files are paired.
+    ///
+    /// A deleted file is renamed to the last added file it is paired with, all other pairs are
+    /// copies.
+    pub(crate) fn find(repo: &Repository, old: Option<&Tree<'_>>, diff: &Diff<'_>) -> Result<Self> {
+        let mut destinations = Vec::new();
+        let mut modified = HashSet::new();
+        let mut deleted = HashSet::new();
+        for delta in diff.deltas() {
+            match delta.status() {
+                Delta::Added => {
+                    if let Some(path) = delta.new_file().path_bytes() {
+                        destinations.push(Destination {
+                            path,
+                            oid: delta.new_file().id(),
+                            mode: delta.new_file().mode().into(),
+                            source: None,
+                        });
+                    }
+                }
+                Delta::Deleted => {
+                    deleted.extend(delta.old_file().path_bytes());
+                }
+                Delta::Modified => {
+                    modified.extend(delta.old_file().path_bytes());
+                }
+                _ => {}
+            }
+        }
+        let Some(old) = old.filter(|_| !destinations.is_empty()) else {
+            return Ok(Self::default());
+        };
+
+        let mut sources = Vec::new();
+        old.walk(TreeWalkMode::PreOrder, |root, entry| {
+            let mode = entry.filemode();
+            // trees are walked, submodules are never paired
+            if !is_blob(mode) {
+                return TreeWalkResult::Ok;
+            }
+            let path = [root.as_bytes(), entry.name_bytes()].concat();
+            let stays = !deleted.contains(path.as_slice());
+            sources.push(Source {
+                path,
+                oid: entry.id(),
+                mode,
+                stays,
+                used: usize::from(stays),
+            });
+            TreeWalkResult::Ok
+        })?;
+
+        // identical files, preferring unused sources with the same name
+        let mut by_oid: HashMap<Oid, Vec<usize>> = HashMap::new();
+        for (idx, source) in sources.iter().enumerate() {
+            by_oid.entry(source.oid).or_default().push(idx);
+        }
+        for destination in &mut destinations {
+            let mut best = None;
+            for &idx in by_oid.get(&destination.oid).into_iter().flatten() {
+                let source = &sources[idx];
+                if !(is_regular(source.mode) && is_regular(destination.mode))
+                    && source.mode != destination.mode
+                {
+                    continue;
+                }
+                let score = usize::from(source.used == 0)
+                    + usize::from(same_name(&source.path, destination.path));
+                if best.is_none_or(|(_, best)| score > best) {
+                    best = Some((idx, score));
+                    if score == 2 {
+                        break;
+                    }
+                }
+            }
+            if let Some((idx, _)) = best {
+                sources[idx].used += 1;
+                destination.source = Some(idx);
+            }
+        }
+
+        let remaining = destinations.iter().filter(|d| d.source.is_none()).count() as u64;
+        let changed = sources
+            .iter()
+            .filter(|s| !s.stays || modified.contains(s.path.as_slice()))
+            .count() as u64;
+        let limit = RENAME_LIMIT * RENAME_LIMIT;
+        if remaining > 0 && remaining * changed <= limit {
+            let only_changed = remaining * sources.len() as u64 > limit;
+            let mut blobs = Blobs {
+                repo,
+                odb: repo.odb()?,
+                sizes: HashMap::new(),
+                spans: HashMap::new(),
+            };
+            let mut candidates = Vec::new();
+            for (destination_idx, destination) in destinations.iter().enumerate() {
+                if destination.source.is_some() {
+                    continue;
+                }
+                let mut best: [Option<Candidate>; CANDIDATES] = [None; CANDIDATES];
+                if is_regular(destination.mode) {
+                    let destination_size = blobs.size(destination.oid)?;
+                    for (source_idx, source) in sources.iter().enumerate() {
+                        if only_changed
+                            && source.stays
+                            && !modified.contains(source.path.as_slice())
+                        {
+                            continue;
+                        }
+                        if !is_regular(source.mode) {
+                            continue;
+                        }
+                        let source_size = blobs.size(source.oid)?;
+                        let max_size = source_size.max(destination_size);
+                        let delta_size = max_size - source_size.min(destination_size);
+                        // too different in size to reach the minimum score
+                        if max_size * (MAX_SCORE - MIN_SCORE) < delta_size * MAX_SCORE {
+                            continue;
+                        }
+                        let copied = blobs.copied_bytes(source.oid, destination.oid)?;
+                        let candidate = Candidate {
+                            destination: destination_idx,
+                            source: source_idx,
+                            score: if destination_size == 0 {
+                                0
+                            } else {
+                                copied * MAX_SCORE / max_size
+                            },
+                            same_name: same_name(&source.path, destination.path),
+                        };
+                        record_if_better(&mut best, candidate);
+                    }
+                }
+                candidates.extend(best);
+            }
+            // stable, so ties keep the order of the added files
+            let mut candidates: Vec<_> = candidates.into_iter().flatten().collect();
+            candidates.sort_by(|a, b| b.score.cmp(&a.score).then(b.same_name.cmp(&a.same_name)));
+            // renames first, then copies
+            for copies in [false, true] {
+                for candidate in &candidates {
+                    if candidate.score < MIN_SCORE {
+                        break;
+                    }
+                    let destination = &mut destinations[candidate.destination];
+                    if destination.source.is_some()
+                        || (!copies && sources[candidate.source].used > 0)
+                    {
+                        continue;
+                    }
+                    sources[candidate.source].used += 1;
+                    destination.source = Some(candidate.source);
+                }
+            }
+        }
+
+        let mut pairs = Self::default();
+        for destination in destinations {
+            let Some(idx) = destination.source else {
+                continue;
+            };
+            let source = &mut sources[idx];
+            source.used -= 1;
+            if source.used == 0 {
+                pairs.renamed.insert(destination.path.to_vec());
+                pairs.moved.insert(source.path.clone());
+            } else {
+                pairs.copies.insert(destination.path.to_vec(), source.oid);
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// Blob of the file the added file at `path` was copied from.
+    pub(crate) fn source(&self, path: &[u8]) -> Option<Oid> {
+        self.copies.get(path).copied()
+    }
+
+    /// Whether the added file at `path` was renamed from a deleted file.
+    pub(crate) fn is_renamed(&self, path: &[u8]) -> bool {
+        self.renamed.contains(path)
+    }
+
+    /// Whether the deleted file at `path` was renamed to an added file.
+    pub(crate) fn is_moved(&self, path: &[u8]) -> bool {
+        self.moved.contains(path)
+    }
+}
+
+fn is_blob(mode: i32) -> bool {
+    matches!(mode & MODE_TYPE, 0o100_000 | 0o120_000)
+}
+
+fn is_regular(mode: i32) -> bool {
+    mode & MODE_TYPE == MODE_REGULAR
+}
+
+fn same_name(a: &[u8], b: &[u8]) -> bool {
+    a.rsplit(|&c| c == b'/').next() == b.rsplit(|&c| c == b'/').next()
+}
+
+/// Keep the best candidates, earlier candidates win ties.
+fn record_if_better(best: &mut [Option<Candidate>; CANDIDATES], candidate: Candidate) {
+    let rank = |c: &Option<Candidate>| c.map(|c| (c.score, c.same_name));
+    let worst = (1..CANDIDATES).fold(0, |worst, idx| {
+        if rank(&best[idx]) < rank(&best[worst]) {
+            idx
+        } else {
+            worst
+        }
+    });
+    if rank(&best[worst]) < rank(&Some(candidate)) {
+        best[worst] = Some(candidate);
+    }
+}
+
+/// Sizes and span hashes of the blobs that were compared so far.
+struct Blobs<'r> {
+    repo: &'r Repository,
+    odb: Odb<'r>,
+    sizes: HashMap<Oid, u64>,
+    spans: HashMap<Oid, HashMap<u32, u64>>,
+}
+
+impl Blobs<'_> {
+    fn size(&mut self, oid: Oid) -> Result<u64> {
+        if let Some(size) = self.sizes.get(&oid) {
+            return Ok(*size);
+        }
+        let size = self.odb.read_header(oid)?.0 as u64;
+        self.sizes.insert(oid, size);
+        Ok(size)
+    }
+
+    /// Number of bytes of `source` that are also in `destination`.
+    fn copied_bytes(&mut self, source: Oid, destination: Oid) -> Result<u64> {
+        for oid in [source, destination] {
+            if !self.spans.contains_key(&oid) {
+                let spans = hash_spans(self.repo.find_blob(oid)?.content());
+                self.spans.insert(oid, spans);
+            }
+        }
+        Ok(copied_bytes(
+            &self.spans[&source],
+            &self.spans[&destination],
+        ))
+    }
+}
+
+fn copied_bytes(source: &HashMap<u32, u64>, destination: &HashMap<u32, u64>) -> u64 {
+    source
+        .iter()
+        .map(|(hash, count)| (*count).min(destination.get(hash).copied().unwrap_or_default()))
+        .sum()
+}
+
+/// Split `content` into lines, or chunks of 64 bytes for long lines, and count the bytes per hash
+/// of the spans, like `hash_chars` in git.
+fn hash_spans(content: &[u8]) -> HashMap<u32, u64> {
+    let text = !content[..content.len().min(FIRST_FEW_BYTES)].contains(&0);
+    let mut spans = HashMap::new();
+    let (mut accum1, mut accum2, mut n) = (0u32, 0u32, 0u64);
+    let mut bytes = content.iter().copied().peekable();
+    while let Some(c) = bytes.next() {
+        // CR in CRLF is ignored in text files
+        if text && c == b'\r' && bytes.peek() == Some(&b'\n') {
+            continue;
+        }
+        let old1 = accum1;
+        accum1 = (accum1 << 7) ^ (accum2 >> 25);
+        accum2 = (accum2 << 7) ^ (old1 >> 25);
+        accum1 = accum1.wrapping_add(u32::from(c));
+        n += 1;
+        if n < 64 && c != b'\n' {
+            continue;
+        }
+        *spans.entry(span_hash(accum1, accum2)).or_default() += n;
+        (accum1, accum2, n) = (0, 0, 0);
+    }
+    if n > 0 {
+        *spans.entry(span_hash(accum1, accum2)).or_default() += n;
+    }
+    spans
+}
+
+fn span_hash(accum1: u32, accum2: u32) -> u32 {
+    accum1.wrapping_add(accum2.wrapping_mul(0x61)) % HASH_BASE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_SCORE, copied_bytes, hash_spans};
+
+    /// Similarity index in percent, like `git diff -C` shows it.
+    fn similarity(source: &str, destination: &str) -> u64 {
+        let copied = copied_bytes(
+            &hash_spans(source.as_bytes()),
+            &hash_spans(destination.as_bytes()),
+        );
+        copied * MAX_SCORE / source.len().max(destination.len()) as u64 * 100 / MAX_SCORE
+    }
+
+    #[test]
+    fn similarity_index() {
+        let lines: String = (1..=10).map(|i| format!("line {i}\n")).collect();
+        let copy = format!("{}line 11\n", lines.replace("line 5\n", "changed 5\n"));
+        assert_eq!(similarity(&lines, &copy), 78);
+        let similar: String = (1..=4)
+            .map(|i| format!("line {i}\n"))
+            .chain((1..=6).map(|i| format!("other {i}\n")))
+            .collect();
+        assert_eq!(similarity(&lines, &similar), 36);
+        assert_eq!(similarity(&lines, &lines.replace("line", "    line")), 0);
+        // CR in CRLF is not hashed, but still counts to the size
+        assert_eq!(similarity(&lines, &lines.replace('\n', "\r\n")), 87);
+    }
+}
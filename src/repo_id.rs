@@ -0,0 +1,175 @@
+use crate::{
+    error::{Error, Result},
+    platform::Platform,
+};
+
+use std::{
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+/// Identity of a repository on a platform.
+///
+/// Most forges treat owner and repository names case-insensitively, so `Vbrandl/HoC` and
+/// `vbrandl/hoc` are the same repository. On those platforms comparison, hashing and storage
+/// paths ignore the case, while the spelling used to create the id is kept for links. Plain git
+/// remotes and local directories keep the exact case.
+#[derive(Clone, Debug)]
+pub struct RepoId {
+    platform: Platform,
+    owner: String,
+    repo: String,
+    /// `owner` and `repo` used for comparison and storage paths, lowercase if the platform is
+    /// case-insensitive
+    owner_key: String,
+    repo_key: String,
+}
+
+impl RepoId {
+    /// Create a new repository id.
+    ///
+    /// The owner may consist of multiple segments (e.g. GitLab subgroups). Empty and relative
+    /// segments (`.` and `..`) are rejected, since the id is used to build filesystem paths.
+    pub(crate) fn new(
+        platform: Platform,
+        owner: impl Into<String>,
+        repo: impl Into<String>,
+    ) -> Result<Self> {
+        let owner = owner.into();
+        let repo = repo.into();
+        let invalid_segment = |s: &str| s.is_empty() || s == "." || s == "..";
+        if owner.split('/').any(invalid_segment) || invalid_segment(&repo) || repo.contains('/') {
+            return Err(Error::InvalidRepo(format!("{owner}/{repo}")));
+        }
+        let (owner_key, repo_key) = if platform.case_insensitive() {
+            (owner.to_lowercase(), repo.to_lowercase())
+        } else {
+            (owner.clone(), repo.clone())
+        };
+        Ok(Self {
+            platform,
+            owner_key,
+            repo_key,
+            owner,
+            repo,
+        })
+    }
+
+    /// The same repository under a different owner or name, e.g. after it was renamed.
+    pub(crate) fn renamed(
+        &self,
+        owner: impl Into<String>,
+        repo: impl Into<String>,
+    ) -> Result<Self> {
+        Self::new(self.platform.clone(), owner, repo)
+    }
+
+    pub(crate) fn platform(&self) -> &Platform {
+        &self.platform
+    }
+
+    pub(crate) fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    pub(crate) fn repo(&self) -> &str {
+        &self.repo
+    }
+
+    /// Directory of the repository below `root`.
+    ///
+    /// The layout is `<platform>/<owner>/<repo>`. Nested namespaces are encoded into a single
+    /// owner directory.
+    pub(crate) fn path(&self, root: &Path) -> PathBuf {
+        root.join(self.platform.domain())
+            .join(urlencoding::encode(&self.owner_key).as_ref())
+            .join(&self.repo_key)
+    }
+}
+
+impl PartialEq for RepoId {
+    fn eq(&self, other: &Self) -> bool {
+        self.platform == other.platform
+            && self.owner_key == other.owner_key
+            && self.repo_key == other.repo_key
+    }
+}
+
+impl Eq for RepoId {}
+
+impl Hash for RepoId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.platform.hash(state);
+        self.owner_key.hash(state);
+        self.repo_key.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RepoId;
+    use crate::platform::Platform;
+
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+        path::Path,
+    };
+
+    fn hash(id: &RepoId) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let a = RepoId::new(Platform::GitHub, "Vbrandl", "HoC").unwrap();
+        let b = RepoId::new(Platform::GitHub, "vbrandl", "hoc").unwrap();
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_eq!(a.path(Path::new("repos")), b.path(Path::new("repos")));
+        assert_eq!(a.owner(), "Vbrandl");
+    }
+
+    #[test]
+    fn case_sensitive() {
+        let a = RepoId::new(Platform::Local, "a", "Repo").unwrap();
+        let b = RepoId::new(Platform::Local, "a", "repo").unwrap();
+        assert_ne!(a, b);
+        assert_ne!(a.path(Path::new("repos")), b.path(Path::new("repos")));
+
+        let host: std::sync::Arc<str> = "host".into();
+        assert_ne!(
+            RepoId::new(Platform::Git(host.clone()), "Foo", "x").unwrap(),
+            RepoId::new(Platform::Git(host), "foo", "x").unwrap()
+        );
+    }
+
+    #[test]
+    fn different_platform() {
+        assert_ne!(
+            RepoId::new(Platform::GitHub, "vbrandl", "hoc").unwrap(),
+            RepoId::new(Platform::Gitlab, "vbrandl", "hoc").unwrap()
+        );
+    }
+
+    #[test]
+    fn nested_namespace() {
+        let id = RepoId::new(Platform::Gitlab, "Group/Sub", "Project").unwrap();
+        assert_eq!(
+            id.path(Path::new("repos")),
+            Path::new("repos/gitlab.com/group%2Fsub/project")
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(RepoId::new(Platform::GitHub, "", "hoc").is_err());
+        assert!(RepoId::new(Platform::GitHub, "vbrandl", "").is_err());
+        assert!(RepoId::new(Platform::GitHub, "..", "hoc").is_err());
+        assert!(RepoId::new(Platform::GitHub, "group/../other", "hoc").is_err());
+        assert!(RepoId::new(Platform::GitHub, "vbrandl", ".").is_err());
+        assert!(RepoId::new(Platform::GitHub, "group//sub", "hoc").is_err());
+    }
+}
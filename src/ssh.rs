@@ -0,0 +1,112 @@
+use std::{fs, path::Path};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use tracing::warn;
+
+/// Check if `key` is listed for `host` in a `known_hosts` file.
+///
+/// Hashed hostnames, wildcards and markers like `@cert-authority` are not supported and such
+/// entries never match.
+pub(crate) fn is_known_host(
+    known_hosts: &Path,
+    host: &str,
+    port: Option<u16>,
+    key_type: &str,
+    key: &[u8],
+) -> bool {
+    match fs::read_to_string(known_hosts) {
+        Ok(content) => matches_known_hosts(&content, host, port, key_type, key),
+        Err(err) => {
+            warn!(%err, known_hosts = %known_hosts.display(), "cannot read known_hosts");
+            false
+        }
+    }
+}
+
+fn matches_known_hosts(
+    content: &str,
+    host: &str,
+    port: Option<u16>,
+    key_type: &str,
+    key: &[u8],
+) -> bool {
+    let host = match port {
+        Some(port) if port != 22 => format!("[{host}]:{port}"),
+        _ => host.to_string(),
+    };
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('@'))
+        .any(|line| {
+            let mut parts = line.split_whitespace();
+            let (Some(hosts), Some(entry_type), Some(entry_key)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                return false;
+            };
+            hosts.split(',').any(|h| h.eq_ignore_ascii_case(&host))
+                && entry_type == key_type
+                && STANDARD
+                    .decode(entry_key)
+                    .is_ok_and(|entry_key| entry_key == key)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches_known_hosts;
+
+    const KNOWN_HOSTS: &str = "\
+# comment
+git.example.com,10.0.0.1 ssh-ed25519 AAECAw==
+[git.example.org]:2222 ssh-ed25519 BAUG
+";
+
+    #[test]
+    fn known_host() {
+        assert!(matches_known_hosts(
+            KNOWN_HOSTS,
+            "git.example.com",
+            None,
+            "ssh-ed25519",
+            &[0, 1, 2, 3]
+        ));
+    }
+
+    #[test]
+    fn known_host_with_port() {
+        assert!(matches_known_hosts(
+            KNOWN_HOSTS,
+            "git.example.org",
+            Some(2222),
+            "ssh-ed25519",
+            &[4, 5, 6]
+        ));
+        assert!(!matches_known_hosts(
+            KNOWN_HOSTS,
+            "git.example.org",
+            None,
+            "ssh-ed25519",
+            &[4, 5, 6]
+        ));
+    }
+
+    #[test]
+    fn wrong_key() {
+        assert!(!matches_known_hosts(
+            KNOWN_HOSTS,
+            "git.example.com",
+            None,
+            "ssh-ed25519",
+            &[4, 5, 6]
+        ));
+        assert!(!matches_known_hosts(
+            KNOWN_HOSTS,
+            "git.example.com",
+            None,
+            "ssh-rsa",
+            &[0, 1, 2, 3]
+        ));
+    }
+}
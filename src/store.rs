@@ -0,0 +1,653 @@
+use crate::{diff::FileStat, error::Result};
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::{self, File, OpenOptions, create_dir_all, rename},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use git2::Oid;
+use serde::{Deserialize, Serialize, de::IgnoredAny};
+use tracing::{debug, warn};
+
+/// Size of an entry in the index file: the commit, followed by the offset and length of its
+/// record in the store file as little endian integers.
+const INDEX_ENTRY: usize = 20 + 8 + 8;
+
+/// A line in the store file.
+#[derive(Serialize, Deserialize)]
+struct Record<'a> {
+    commit: String,
+    /// Path, additions and deletions of every changed text file
+    #[serde(borrow)]
+    files: Vec<(Cow<'a, str>, u64, u64)>,
+    /// Path, additions and deletions of every changed binary file. Missing in records written
+    /// before binary files were stored
+    #[serde(borrow, default)]
+    binary: Option<Vec<(Cow<'a, str>, u64, u64)>>,
+}
+
+/// The fields of a [`Record`] that are needed to decide, whether it is read.
+#[derive(Deserialize)]
+struct Key<'a> {
+    commit: &'a str,
+    #[serde(default)]
+    binary: Option<IgnoredAny>,
+}
+
+impl Key<'_> {
+    /// Parse the commit of a line, if the line is a valid record.
+    fn parse(line: &str) -> Option<Oid> {
+        let key = serde_json::from_str::<Key<'_>>(line).ok()?;
+        let oid = Oid::from_str(key.commit).ok()?;
+        key.binary.map(|_| oid)
+    }
+}
+
+/// Position of a record in the store file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Span {
+    offset: u64,
+    /// Length without the newline
+    len: u64,
+}
+
+impl Span {
+    fn end(self) -> u64 {
+        self.offset + self.len + 1
+    }
+}
+
+/// Numstat of every commit that was diffed so far.
+///
+/// The stats of a commit never change, so they are shared by all branches, ranges and
+/// exclude/include combinations of a repository. New commits are appended to a JSON lines file,
+/// so calculating another variant of a badge only walks the history instead of diffing it again.
+///
+/// An index file maps every commit to the position of its record, so only the records of the
+/// walked commits are read, one at a time. Records appended without updating the index, e.g. by
+/// an older version, are indexed when the store is opened.
+///
+/// The store is locked while it is open, so counts of the same repository wait for each other
+/// and diff every commit only once.
+pub(crate) struct CommitStore {
+    path: PathBuf,
+    /// Held until the store is dropped
+    _lock: File,
+    index: HashMap<Oid, Span>,
+    /// Store file, opened the first time a record is read
+    reader: Option<File>,
+    /// Store file, opened the first time a commit is inserted
+    writer: Option<BufWriter<File>>,
+    /// Length of the store file, including records that are not flushed yet
+    len: u64,
+    /// Index entries that are not written to the index file yet
+    pending: Vec<(Oid, Span)>,
+    /// Whether the index file must be written from scratch, e.g. because it is truncated
+    rewrite_index: bool,
+    /// Whether the store file contains duplicate, outdated or malformed lines
+    compact: bool,
+}
+
+impl CommitStore {
+    /// Open the store at `path`, waiting until no other count uses it.
+    ///
+    /// Malformed lines, e.g. from an interrupted write, and outdated records are skipped and
+    /// computed again. They are removed, along with duplicates, when the store is saved.
+    pub(crate) fn open(path: PathBuf) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path.with_extension("lock"))?;
+        lock.lock()?;
+        let len = match fs::metadata(&path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => Err(e)?,
+        };
+        let mut store = Self {
+            path,
+            _lock: lock,
+            index: HashMap::new(),
+            reader: None,
+            writer: None,
+            len,
+            pending: Vec::new(),
+            rewrite_index: false,
+            compact: false,
+        };
+        let indexed = store.read_index()?;
+        store.index_tail(indexed)?;
+        debug!(commits = store.index.len(), "opened numstat store");
+        Ok(store)
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.path.with_extension("idx")
+    }
+
+    /// Read the index file and return the end of the indexed records.
+    fn read_index(&mut self) -> Result<u64> {
+        let bytes = match fs::read(self.index_path()) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.rewrite_index = self.len > 0;
+                return Ok(0);
+            }
+            Err(e) => Err(e)?,
+        };
+        let entries = bytes.chunks_exact(INDEX_ENTRY);
+        // e.g. an interrupted write
+        self.rewrite_index = !entries.remainder().is_empty();
+        let mut end = 0;
+        for entry in entries {
+            let (oid, span) = entry.split_at(20);
+            let (offset, len) = span.split_at(8);
+            let span = Span {
+                offset: u64::from_le_bytes(offset.try_into().expect("8 bytes")),
+                len: u64::from_le_bytes(len.try_into().expect("8 bytes")),
+            };
+            if span.end() > self.len {
+                // the store file was replaced or truncated
+                warn!(path = %self.path.display(), "numstat index is outdated, rebuilding");
+                self.index.clear();
+                self.rewrite_index = true;
+                return Ok(0);
+            }
+            self.index.insert(Oid::from_bytes(oid)?, span);
+            end = end.max(span.end());
+        }
+        Ok(end)
+    }
+
+    /// Index the records after `start`, that were appended without updating the index.
+    fn index_tail(&mut self, start: u64) -> Result<()> {
+        if start >= self.len {
+            return Ok(());
+        }
+        let mut f = File::open(&self.path)?;
+        f.seek(SeekFrom::Start(start))?;
+        let mut reader = BufReader::new(f);
+        let mut offset = start;
+        let mut line = String::new();
+        let mut skipped = 0;
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)? as u64;
+            if read == 0 {
+                break;
+            }
+            let record = line.trim_end_matches('\n');
+            match Key::parse(record) {
+                Some(oid) if !self.index.contains_key(&oid) => {
+                    let span = Span {
+                        offset,
+                        len: record.len() as u64,
+                    };
+                    self.index.insert(oid, span);
+                    self.pending.push((oid, span));
+                }
+                _ => skipped += 1,
+            }
+            offset += read;
+        }
+        if skipped > 0 {
+            warn!(path = %self.path.display(), skipped, "skipping outdated or malformed numstat records");
+            self.compact = true;
+        }
+        Ok(())
+    }
+
+    /// Read the stats of `oid`, if they are stored.
+    pub(crate) fn get(&mut self, oid: Oid) -> Result<Option<Vec<FileStat>>> {
+        let Some(&span) = self.index.get(&oid) else {
+            return Ok(None);
+        };
+        if let Some(files) = self.read(oid, span)? {
+            return Ok(Some(files));
+        }
+        // e.g. the store file was replaced or a compaction was interrupted
+        warn!(path = %self.path.display(), %oid, "numstat index does not match the store, rebuilding");
+        self.index.clear();
+        self.pending.clear();
+        self.rewrite_index = true;
+        self.index_tail(0)?;
+        let Some(&span) = self.index.get(&oid) else {
+            return Ok(None);
+        };
+        let files = self.read(oid, span)?;
+        if files.is_none() {
+            self.index.remove(&oid);
+            self.compact = true;
+        }
+        Ok(files)
+    }
+
+    /// Read the record at `span`, if it contains the stats of `oid`.
+    fn read(&mut self, oid: Oid, span: Span) -> Result<Option<Vec<FileStat>>> {
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+        }
+        let reader = match &mut self.reader {
+            Some(reader) => reader,
+            None => self.reader.insert(File::open(&self.path)?),
+        };
+        reader.seek(SeekFrom::Start(span.offset))?;
+        let mut line = vec![0; usize::try_from(span.len).unwrap_or(usize::MAX)];
+        reader.read_exact(&mut line)?;
+        let record = std::str::from_utf8(&line)
+            .ok()
+            .and_then(|line| serde_json::from_str::<Record<'_>>(line).ok())
+            .filter(|record| record.commit == oid.to_string() && record.binary.is_some());
+        Ok(record.map(|record| {
+            record
+                .files
+                .into_iter()
+                .map(|file| (file, false))
+                .chain(record.binary.into_iter().flatten().map(|file| (file, true)))
+                .map(|((path, additions, deletions), binary)| FileStat {
+                    path: path.into_owned(),
+                    additions,
+                    deletions,
+                    binary,
+                })
+                .collect()
+        }))
+    }
+
+    /// Append the stats of `oid` to the store file. They are not kept in memory.
+    pub(crate) fn insert(&mut self, oid: Oid, files: &[FileStat]) -> Result<()> {
+        if self.index.contains_key(&oid) {
+            return Ok(());
+        }
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                let mut f = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .read(true)
+                    .open(&self.path)?;
+                // a record that was cut off must not swallow the next one
+                if self.len > 0 {
+                    let mut last = [0];
+                    f.seek(SeekFrom::Start(self.len - 1))?;
+                    f.read_exact(&mut last)?;
+                    if last != *b"\n" {
+                        f.write_all(b"\n")?;
+                        self.len += 1;
+                    }
+                }
+                self.writer.insert(BufWriter::new(f))
+            }
+        };
+        let (binary, files): (Vec<_>, Vec<_>) = files
+            .iter()
+            .map(|file| {
+                (
+                    file.binary,
+                    (file.path.as_str().into(), file.additions, file.deletions),
+                )
+            })
+            .partition(|(binary, _)| *binary);
+        let record = Record {
+            commit: oid.to_string(),
+            files: files.into_iter().map(|(_, file)| file).collect(),
+            binary: Some(binary.into_iter().map(|(_, file)| file).collect()),
+        };
+        let line = serde_json::to_vec(&record)?;
+        writer.write_all(&line)?;
+        writer.write_all(b"\n")?;
+        let span = Span {
+            offset: self.len,
+            len: line.len() as u64,
+        };
+        self.len = span.end();
+        self.index.insert(oid, span);
+        self.pending.push((oid, span));
+        Ok(())
+    }
+
+    /// Write the new index entries and release the lock.
+    ///
+    /// If the file contains lines that were skipped, it is rewritten with only the indexed
+    /// records.
+    pub(crate) fn save(mut self) -> Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer
+                .into_inner()
+                .map_err(io::IntoInnerError::into_error)?
+                .sync_all()?;
+        }
+        if self.compact {
+            self.compact_store()?;
+        }
+        if self.rewrite_index || self.compact {
+            let tmp = self.index_path().with_extension("idx.tmp");
+            let mut writer = BufWriter::new(File::create(&tmp)?);
+            for (oid, span) in &self.index {
+                write_entry(&mut writer, *oid, *span)?;
+            }
+            writer
+                .into_inner()
+                .map_err(io::IntoInnerError::into_error)?
+                .sync_all()?;
+            rename(&tmp, self.index_path())?;
+        } else if !self.pending.is_empty() {
+            let mut writer = BufWriter::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(self.index_path())?,
+            );
+            for (oid, span) in &self.pending {
+                write_entry(&mut writer, *oid, *span)?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the store file with only the indexed records.
+    fn compact_store(&mut self) -> Result<()> {
+        let tmp = self.path.with_extension("jsonl.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        let mut reader = File::open(&self.path)?;
+        let mut spans: Vec<_> = self.index.drain().collect();
+        spans.sort_by_key(|(_, span)| span.offset);
+        let mut offset = 0;
+        for (oid, span) in spans {
+            reader.seek(SeekFrom::Start(span.offset))?;
+            let mut line = vec![0; usize::try_from(span.len).unwrap_or(usize::MAX)];
+            reader.read_exact(&mut line)?;
+            if std::str::from_utf8(&line).ok().and_then(Key::parse) != Some(oid) {
+                continue;
+            }
+            writer.write_all(&line)?;
+            writer.write_all(b"\n")?;
+            let span = Span {
+                offset,
+                len: span.len,
+            };
+            offset = span.end();
+            self.index.insert(oid, span);
+        }
+        writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)?
+            .sync_all()?;
+        rename(&tmp, &self.path)?;
+        debug!(path = %self.path.display(), commits = self.index.len(), "compacted numstat store");
+        Ok(())
+    }
+}
+
+fn write_entry(writer: &mut impl Write, oid: Oid, span: Span) -> Result<()> {
+    writer.write_all(oid.as_bytes())?;
+    writer.write_all(&span.offset.to_le_bytes())?;
+    writer.write_all(&span.len.to_le_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommitStore;
+    use crate::diff::FileStat;
+
+    use std::{
+        path::Path,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread,
+        time::Duration,
+    };
+
+    use git2::Oid;
+    use tempfile::tempdir;
+
+    const FIRST: &str = "1f01c3b964b018fb0c0c2c5b572bf4ace2968546";
+    const SECOND: &str = "2f01c3b964b018fb0c0c2c5b572bf4ace2968546";
+
+    fn stat(path: &str, additions: u64, deletions: u64) -> FileStat {
+        FileStat {
+            path: path.to_string(),
+            additions,
+            deletions,
+            binary: false,
+        }
+    }
+
+    fn binary(path: &str) -> FileStat {
+        FileStat {
+            path: path.to_string(),
+            additions: 1,
+            deletions: 0,
+            binary: true,
+        }
+    }
+
+    fn oid(oid: &str) -> Oid {
+        Oid::from_str(oid).unwrap()
+    }
+
+    fn open(path: &Path) -> CommitStore {
+        CommitStore::open(path.to_path_buf()).unwrap()
+    }
+
+    fn get(store: &mut CommitStore, commit: &str) -> Option<Vec<FileStat>> {
+        store.get(oid(commit)).unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("repo").join("numstat.jsonl");
+
+        let mut store = open(&path);
+        assert!(get(&mut store, FIRST).is_none());
+        store
+            .insert(
+                oid(FIRST),
+                &[
+                    stat("a.rs", 1, 2),
+                    stat("dir/b \"c\".rs", 3, 0),
+                    binary("logo.png"),
+                ],
+            )
+            .unwrap();
+        store.save().unwrap();
+
+        let mut store = open(&path);
+        store.insert(oid(SECOND), &[]).unwrap();
+        // already stored
+        store
+            .insert(
+                oid(FIRST),
+                &[stat("a.rs", 1, 2), stat("dir/b \"c\".rs", 3, 0)],
+            )
+            .unwrap();
+        assert_eq!(get(&mut store, SECOND).unwrap(), &[]);
+        store.save().unwrap();
+
+        let mut store = open(&path);
+        assert_eq!(
+            get(&mut store, FIRST).unwrap(),
+            &[
+                stat("a.rs", 1, 2),
+                stat("dir/b \"c\".rs", 3, 0),
+                binary("logo.png")
+            ]
+        );
+        assert_eq!(get(&mut store, SECOND).unwrap(), &[]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        assert_eq!(
+            std::fs::metadata(path.with_extension("idx")).unwrap().len(),
+            2 * super::INDEX_ENTRY as u64
+        );
+    }
+
+    #[test]
+    fn index_store_without_index() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("numstat.jsonl");
+        std::fs::write(
+            &path,
+            format!(
+                "{{\"commit\":\"{FIRST}\",\"files\":[[\"a\",1,0]],\"binary\":[]}}\n{{\"commit\":\"{SECOND}\",\"files\":[],\"binary\":[]}}\n"
+            ),
+        )
+        .unwrap();
+        let mut store = open(&path);
+        assert_eq!(get(&mut store, FIRST).unwrap(), &[stat("a", 1, 0)]);
+        store.save().unwrap();
+        assert!(path.with_extension("idx").exists());
+
+        let mut store = open(&path);
+        assert_eq!(get(&mut store, SECOND).unwrap(), &[]);
+    }
+
+    #[test]
+    fn index_appended_records() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("numstat.jsonl");
+        let mut store = open(&path);
+        store.insert(oid(FIRST), &[stat("a", 1, 0)]).unwrap();
+        store.save().unwrap();
+
+        // e.g. the index was not written before the process was killed
+        let mut f = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        std::io::Write::write_all(
+            &mut f,
+            format!("{{\"commit\":\"{SECOND}\",\"files\":[[\"b\",2,0]],\"binary\":[]}}\n")
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let mut store = open(&path);
+        assert_eq!(get(&mut store, FIRST).unwrap(), &[stat("a", 1, 0)]);
+        assert_eq!(get(&mut store, SECOND).unwrap(), &[stat("b", 2, 0)]);
+    }
+
+    #[test]
+    fn skip_malformed_lines() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("numstat.jsonl");
+        std::fs::write(
+            &path,
+            format!("{{\"commit\":\"{FIRST}\",\"files\":[[\"a\",1,0]],\"binary\":[]}}\n{{\"commit\":\"2f0"),
+        )
+        .unwrap();
+        let mut store = open(&path);
+        assert_eq!(get(&mut store, FIRST).unwrap(), &[stat("a", 1, 0)]);
+
+        // the cut off line does not swallow the new record
+        store.insert(oid(SECOND), &[stat("b", 1, 0)]).unwrap();
+        store.save().unwrap();
+        let mut store = open(&path);
+        assert_eq!(get(&mut store, SECOND).unwrap(), &[stat("b", 1, 0)]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn skip_records_without_binary_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("numstat.jsonl");
+        std::fs::write(
+            &path,
+            format!("{{\"commit\":\"{FIRST}\",\"files\":[[\"a\",1,0]]}}\n"),
+        )
+        .unwrap();
+        let mut store = open(&path);
+        assert!(get(&mut store, FIRST).is_none());
+    }
+
+    #[test]
+    fn compact_on_save() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("numstat.jsonl");
+        let first = format!("{{\"commit\":\"{FIRST}\",\"files\":[[\"a\",1,0]],\"binary\":[]}}");
+        std::fs::write(
+            &path,
+            format!(
+                "{first}\n{first}\n{{\"commit\":\"{SECOND}\",\"files\":[[\"b\",1,0]]}}\n{{\"commit\":\"3f0\n"
+            ),
+        )
+        .unwrap();
+
+        // the duplicate, the outdated record and the malformed line are removed
+        let mut store = open(&path);
+        store.insert(oid(SECOND), &[stat("b", 2, 0)]).unwrap();
+        store.save().unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content.lines().collect::<Vec<_>>(),
+            [
+                first.as_str(),
+                &format!("{{\"commit\":\"{SECOND}\",\"files\":[[\"b\",2,0]],\"binary\":[]}}")
+            ]
+        );
+
+        let mut store = open(&path);
+        assert_eq!(get(&mut store, FIRST).unwrap(), &[stat("a", 1, 0)]);
+        assert_eq!(get(&mut store, SECOND).unwrap(), &[stat("b", 2, 0)]);
+    }
+
+    #[test]
+    fn outdated_index() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("numstat.jsonl");
+        let mut store = open(&path);
+        store.insert(oid(FIRST), &[stat("a", 1, 0)]).unwrap();
+        store.insert(oid(SECOND), &[stat("b", 1, 0)]).unwrap();
+        store.save().unwrap();
+
+        // the records moved, e.g. the store file was replaced by hand
+        std::fs::write(
+            &path,
+            format!(
+                "{{\"commit\":\"{SECOND}\",\"files\":[[\"b\",1,0]],\"binary\":[]}}\n{{\"commit\":\"{FIRST}\",\"files\":[[\"a\",1,0]],\"binary\":[]}}\n"
+            ),
+        )
+        .unwrap();
+        let mut store = open(&path);
+        assert_eq!(get(&mut store, FIRST).unwrap(), &[stat("a", 1, 0)]);
+        store.save().unwrap();
+
+        let mut store = open(&path);
+        assert_eq!(get(&mut store, FIRST).unwrap(), &[stat("a", 1, 0)]);
+        assert_eq!(get(&mut store, SECOND).unwrap(), &[stat("b", 1, 0)]);
+    }
+
+    #[test]
+    fn wait_for_lock() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("numstat.jsonl");
+        let store = CommitStore::open(path.clone()).unwrap();
+
+        let opened = Arc::new(AtomicBool::new(false));
+        let waiting = thread::spawn({
+            let opened = Arc::clone(&opened);
+            move || {
+                let _store = CommitStore::open(path).unwrap();
+                opened.store(true, Ordering::SeqCst);
+            }
+        });
+        thread::sleep(Duration::from_millis(100));
+        assert!(!opened.load(Ordering::SeqCst));
+
+        store.save().unwrap();
+        waiting.join().unwrap();
+        assert!(opened.load(Ordering::SeqCst));
+    }
+}
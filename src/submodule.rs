@@ -0,0 +1,143 @@
+use crate::error::Result;
+
+use git2::{ObjectType, Oid, Repository, Sort};
+
+/// A submodule as declared in `.gitmodules`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Submodule {
+    /// Path of the submodule in the repository
+    pub(crate) path: String,
+    /// Remote URL, may be relative to the URL of the repository
+    pub(crate) url: String,
+}
+
+/// Read the submodules declared in `.gitmodules` at `commit`.
+pub(crate) fn submodules(repo: &Repository, commit: Oid) -> Result<Vec<Submodule>> {
+    let tree = repo.find_commit(commit)?.tree()?;
+    let Ok(entry) = tree.get_path(".gitmodules".as_ref()) else {
+        return Ok(Vec::new());
+    };
+    if entry.kind() != Some(ObjectType::Blob) {
+        return Ok(Vec::new());
+    }
+    let blob = repo.find_blob(entry.id())?;
+    Ok(parse(&String::from_utf8_lossy(blob.content())))
+}
+
+/// Commit the submodule at `path` is pinned to at `commit`, if it exists.
+pub(crate) fn pinned(repo: &Repository, commit: Oid, path: &str) -> Result<Option<Oid>> {
+    let tree = repo.find_commit(commit)?.tree()?;
+    Ok(tree
+        .get_path(path.as_ref())
+        .ok()
+        .filter(|entry| entry.kind() == Some(ObjectType::Commit))
+        .map(|entry| entry.id()))
+}
+
+/// Commit the submodule at `path` is pinned to at the start of the range `start..head`.
+///
+/// That is the commit pinned at `start`. If the submodule was added later, or the range has no
+/// start, it is the commit pinned by the first commit of the range that contains the submodule.
+pub(crate) fn range_start(
+    repo: &Repository,
+    start: Option<Oid>,
+    head: Oid,
+    path: &str,
+) -> Result<Option<Oid>> {
+    if let Some(start) = start
+        && let Some(pinned) = pinned(repo, start, path)?
+    {
+        return Ok(Some(pinned));
+    }
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    revwalk.push(head)?;
+    if let Some(start) = start {
+        revwalk.hide(start)?;
+    }
+    for commit in revwalk {
+        if let Some(pinned) = pinned(repo, commit?, path)? {
+            return Ok(Some(pinned));
+        }
+    }
+    Ok(None)
+}
+
+/// Parse the content of a `.gitmodules` file.
+///
+/// Only `path` and `url` of `[submodule "<name>"]` sections are read, submodules missing one of
+/// them are skipped.
+fn parse(content: &str) -> Vec<Submodule> {
+    let mut submodules = Vec::new();
+    // path and url of the current section, `None` outside of submodule sections
+    let mut current: Option<(Option<String>, Option<String>)> = None;
+    let mut finish = |current: Option<(Option<String>, Option<String>)>| {
+        if let Some((Some(path), Some(url))) = current {
+            submodules.push(Submodule { path, url });
+        }
+    };
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if line.starts_with('[') {
+            finish(current.take());
+            if line.starts_with("[submodule") {
+                current = Some((None, None));
+            }
+            continue;
+        }
+        let (Some((path, url)), Some((key, value))) = (current.as_mut(), line.split_once('='))
+        else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_string();
+        match key.trim() {
+            "path" => *path = Some(value.trim_end_matches('/').to_string()),
+            "url" => *url = Some(value),
+            _ => {}
+        }
+    }
+    finish(current);
+    submodules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Submodule, parse};
+
+    #[test]
+    fn parse_gitmodules() {
+        let submodules = parse(
+            r#"
+# comment
+[submodule "lib"]
+	path = vendor/lib
+	url = https://github.com/owner/lib.git
+[core]
+	path = ignored
+	url = ignored
+[submodule "other"]
+	url = "../other"
+	path = other/
+	branch = main
+[submodule "no-url"]
+	path = no-url
+"#,
+        );
+        assert_eq!(
+            submodules,
+            vec![
+                Submodule {
+                    path: "vendor/lib".to_string(),
+                    url: "https://github.com/owner/lib.git".to_string(),
+                },
+                Submodule {
+                    path: "other".to_string(),
+                    url: "../other".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::platform::Platform;
+use crate::{cache::Author, platform::Platform};
 
 #[derive(Clone, Copy)]
 pub struct RepoInfo<'a> {
@@ -8,26 +8,44 @@ pub struct RepoInfo<'a> {
     pub head: &'a str,
     pub hoc: u64,
     pub hoc_pretty: &'a str,
+    pub additions: u64,
+    pub deletions: u64,
+    /// Authors with the most HoC
+    pub authors: &'a [Author],
+    /// Number of all authors
+    pub author_count: usize,
+    /// Languages sorted by HoC
+    pub languages: &'a [LanguageShare],
     pub path: &'a str,
     pub url: &'a str,
     pub branch: &'a str,
     pub query: &'a str,
 }
 
+pub struct LanguageShare {
+    pub name: &'static str,
+    pub color: &'static str,
+    pub hoc: u64,
+    /// Share of the HoC in percent, formatted with one decimal
+    pub percent: String,
+}
+
 pub struct RepoGeneratorInfo<'a> {
     pub platform: Platform,
     pub user: &'a str,
     pub repo: &'a str,
     pub branch: Option<&'a str>,
     pub exclude: Option<&'a str>,
+    pub include: Option<&'a str>,
 }
 
 impl RepoGeneratorInfo<'_> {
     pub fn query(&self) -> String {
         let branch = self.branch.map(|b| format!("branch={b}"));
         let exclude = self.exclude.map(|e| format!("exclude={e}"));
+        let include = self.include.map(|i| format!("include={i}"));
 
-        let query = [branch, exclude]
+        let query = [branch, exclude, include]
             .into_iter()
             .flatten()
             .collect::<Vec<_>>()
@@ -0,0 +1,187 @@
+use crate::{
+    cache::TimelineEntry,
+    error::{Error, Result},
+};
+
+use jiff::{SignedDuration, Timestamp, civil::Date, tz::TimeZone};
+
+/// Time range of commits to count.
+///
+/// Both bounds are optional, so the default window contains all commits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Window {
+    /// Inclusive lower bound
+    since: Option<Timestamp>,
+    /// Exclusive upper bound
+    until: Option<Timestamp>,
+}
+
+impl Window {
+    /// Build a window from the `since`, `until` and `last` query parameters.
+    ///
+    /// `since` and `until` take an RFC 3339 timestamp or a date, which is interpreted in UTC.
+    /// `until` includes the whole day if a date is passed. `last` takes a duration like `30d` and
+    /// cannot be combined with `since`.
+    pub(crate) fn parse(
+        since: Option<&str>,
+        until: Option<&str>,
+        last: Option<&str>,
+        now: Timestamp,
+    ) -> Result<Self> {
+        let since = match (since, last) {
+            (Some(_), Some(_)) => {
+                return Err(Error::InvalidQuery(
+                    "`since` and `last` cannot be combined".to_string(),
+                ));
+            }
+            (Some(since), None) => Some(parse_time(since, false)?),
+            (None, Some(last)) => Some(
+                now.checked_sub(parse_duration(last)?)
+                    .map_err(|_| Error::InvalidQuery(format!("invalid duration `{last}`")))?,
+            ),
+            (None, None) => None,
+        };
+        let until = until.map(|until| parse_time(until, true)).transpose()?;
+        Ok(Self { since, until })
+    }
+
+    pub(crate) fn is_all(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    pub(crate) fn contains(&self, time: Timestamp) -> bool {
+        self.since.is_none_or(|since| time >= since) && self.until.is_none_or(|until| time < until)
+    }
+
+    /// Sum up the additions, deletions, commits and changed binary files in the window.
+    pub(crate) fn count(&self, timeline: &[TimelineEntry]) -> (u64, u64, u64, u64) {
+        timeline
+            .iter()
+            .filter(|entry| {
+                Timestamp::from_second(entry.time).is_ok_and(|time| self.contains(time))
+            })
+            .fold(
+                (0, 0, 0, 0),
+                |(additions, deletions, commits, binary_changes), entry| {
+                    (
+                        additions.saturating_add(entry.additions),
+                        deletions.saturating_add(entry.deletions),
+                        commits + 1,
+                        binary_changes.saturating_add(entry.binary_changes),
+                    )
+                },
+            )
+    }
+}
+
+fn parse_time(value: &str, end_of_day: bool) -> Result<Timestamp> {
+    if let Ok(timestamp) = value.parse::<Timestamp>() {
+        return Ok(timestamp);
+    }
+    let date: Date = value
+        .parse()
+        .map_err(|_| Error::InvalidQuery(format!("invalid time `{value}`")))?;
+    let date = if end_of_day {
+        date.tomorrow()
+            .map_err(|_| Error::InvalidQuery(format!("invalid time `{value}`")))?
+    } else {
+        date
+    };
+    Ok(date
+        .to_zoned(TimeZone::UTC)
+        .map_err(|_| Error::InvalidQuery(format!("invalid time `{value}`")))?
+        .timestamp())
+}
+
+/// Parse a duration like `12h`, `30d` or `2w`.
+fn parse_duration(value: &str) -> Result<SignedDuration> {
+    let invalid = || Error::InvalidQuery(format!("invalid duration `{value}`"));
+    let split = value.len().checked_sub(1).ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at_checked(split).ok_or_else(invalid)?;
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let hours = match unit {
+        "h" => 1,
+        "d" => 24,
+        "w" => 7 * 24,
+        _ => return Err(invalid()),
+    };
+    amount
+        .checked_mul(hours)
+        .filter(|hours| *hours >= 0)
+        .and_then(SignedDuration::try_from_hours)
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Window, parse_duration};
+    use crate::cache::TimelineEntry;
+
+    use jiff::{SignedDuration, Timestamp};
+
+    fn ts(value: &str) -> Timestamp {
+        value.parse().unwrap()
+    }
+
+    fn entry(time: &str, additions: u64, deletions: u64) -> TimelineEntry {
+        TimelineEntry {
+            time: ts(time).as_second(),
+            additions,
+            deletions,
+            binary_changes: 0,
+        }
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(
+            parse_duration("30d").unwrap(),
+            SignedDuration::from_hours(720)
+        );
+        assert_eq!(
+            parse_duration("2w").unwrap(),
+            SignedDuration::from_hours(336)
+        );
+        assert_eq!(
+            parse_duration("12h").unwrap(),
+            SignedDuration::from_hours(12)
+        );
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("-1d").is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn dates_in_utc() {
+        let now = ts("2024-06-01T00:00:00Z");
+        let window = Window::parse(Some("2024-01-01"), Some("2024-01-31"), None, now).unwrap();
+        let timeline = [
+            entry("2023-12-31T23:59:59Z", 1, 0),
+            entry("2024-01-01T00:00:00Z", 2, 1),
+            entry("2024-01-31T23:59:59Z", 3, 0),
+            entry("2024-02-01T00:00:00Z", 4, 0),
+        ];
+        assert_eq!(window.count(&timeline), (5, 1, 2, 0));
+    }
+
+    #[test]
+    fn last_days() {
+        let now = ts("2024-06-01T00:00:00Z");
+        let window = Window::parse(None, None, Some("30d"), now).unwrap();
+        let timeline = [
+            entry("2024-04-01T00:00:00Z", 10, 0),
+            entry("2024-05-15T00:00:00Z", 1, 1),
+        ];
+        assert_eq!(window.count(&timeline), (1, 1, 1, 0));
+    }
+
+    #[test]
+    fn invalid_windows() {
+        let now = Timestamp::UNIX_EPOCH;
+        assert!(Window::parse(Some("2024-01-01"), None, Some("30d"), now).is_err());
+        assert!(Window::parse(Some("yesterday"), None, None, now).is_err());
+        assert!(Window::parse(None, None, Some("999999999w"), now).is_err());
+        assert!(Window::parse(None, None, None, now).unwrap().is_all());
+    }
+}
@@ -53,6 +53,10 @@ impl<T: Hash + Eq + Clone> Queue<T> {
         self.tasks.len()
     }
 
+    pub(crate) fn is_closed(&self) -> bool {
+        !self.active.load(Ordering::SeqCst)
+    }
+
     pub(crate) fn close(&self) {
         info!("closing background worker queue");
         self.active.store(false, Ordering::SeqCst);
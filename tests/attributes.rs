@@ -0,0 +1,49 @@
+mod util;
+
+use std::{fs, time::Duration};
+
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn gitattributes_exclude_files() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    let repo = util::create_repo(
+        &path,
+        &[
+            ("src/main.rs", "fn main() {}\n"),
+            ("src/schema.gen.rs", "struct A;\nstruct B;\nstruct C;\n"),
+            (".gitattributes", "*.gen.rs linguist-generated\n"),
+        ],
+    );
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    // `.gitattributes` itself is counted
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 2);
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?attributes=false").await;
+    assert_eq!(json["count"], 5);
+
+    // removing the attribute recalculates the whole history
+    fs::write(path.join(".gitattributes"), "").unwrap();
+    util::commit(&repo, ".gitattributes", "count generated files");
+
+    let mut count = 0;
+    for _ in 0..100 {
+        count = util::wait_for_json(addr, "/local/owner/repo/json").await["count"]
+            .as_u64()
+            .unwrap();
+        if count != 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(count, 6);
+
+    handle.abort();
+}
@@ -0,0 +1,67 @@
+mod util;
+
+use std::fs;
+
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn authors_resolved_through_mailmap() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    let repo = util::create_repo(&path, &[("a.txt", "a\nb\nc\n")]);
+
+    fs::write(path.join("b.txt"), "a\n").unwrap();
+    util::commit_as(&repo, "b.txt", "other", "Jane", "jane@example.com");
+    fs::write(path.join("b.txt"), "a\nb\n").unwrap();
+    util::commit_as(&repo, "b.txt", "old email", "jane", "jane@old.example.com");
+    fs::write(
+        path.join(".mailmap"),
+        "Jane Doe <jane@example.com>\nJane Doe <jane@example.com> <jane@old.example.com>\n",
+    )
+    .unwrap();
+    util::commit_as(&repo, ".mailmap", "mailmap", "Jane", "jane@example.com");
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/authors").await;
+    let authors = json["authors"].as_array().unwrap();
+    assert_eq!(authors.len(), 2);
+
+    // both identities of Jane are merged
+    assert_eq!(authors[0]["name"], "Jane Doe");
+    assert_eq!(authors[0]["email"], "jane@example.com");
+    assert_eq!(authors[0]["count"], 4);
+    assert_eq!(authors[0]["commits"], 3);
+
+    assert_eq!(authors[1]["name"], "Test");
+    assert_eq!(authors[1]["count"], 3);
+    assert_eq!(authors[1]["commits"], 1);
+
+    // new commits are added to the cached totals
+    fs::write(path.join("a.txt"), "").unwrap();
+    util::commit(&repo, "a.txt", "shorten");
+
+    let mut count = 0;
+    for _ in 0..100 {
+        let json = util::wait_for_json(addr, "/local/owner/repo/authors").await;
+        count = json["authors"][0]["count"].as_u64().unwrap();
+        if json["authors"][0]["name"] == "Test" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(count, 6);
+
+    let overview = reqwest::get(format!("http://{addr}/local/owner/repo/view"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(overview.contains("Jane Doe"));
+
+    handle.abort();
+}
@@ -0,0 +1,80 @@
+mod util;
+
+use std::{fs, path::Path};
+
+use git2::{BranchType, Repository, build::CheckoutBuilder};
+use serde_json::json;
+use tempfile::tempdir;
+
+/// Point `HEAD` to `branch` and check it out.
+fn checkout(repo: &Repository, branch: &str) {
+    repo.set_head(&format!("refs/heads/{branch}")).unwrap();
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))
+        .unwrap();
+}
+
+fn commit_file(repo: &Repository, path: &Path, file: &str, content: &str) {
+    fs::write(path.join(file), content).unwrap();
+    util::commit(repo, file, &format!("change {file}"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn all_branches() {
+    let upstream = tempdir().unwrap();
+    let path = upstream.path().join("owner").join("repo");
+    let repo = util::create_repo(&path, &[("main.txt", "main\n")]);
+    let default_branch = repo.head().unwrap().shorthand().unwrap().to_string();
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    repo.branch("release", &head, false).unwrap();
+    checkout(&repo, "release");
+    commit_file(&repo, &path, "release.txt", "fix a\nfix b\n");
+    checkout(&repo, &default_branch);
+    let main_head = {
+        commit_file(&repo, &path, "feature.txt", "feature\n");
+        repo.head().unwrap().target().unwrap()
+    };
+
+    let clone_url = format!("file://{}/{{owner}}/{{repo}}", upstream.path().display());
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.platforms.push(
+            serde_json::from_value(json!({
+                "name": "upstream",
+                "domain": "upstream.test",
+                "clone_url": clone_url,
+            }))
+            .unwrap(),
+        );
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/upstream/owner/repo/json").await;
+    assert_eq!(json["count"], 2);
+    assert_eq!(json["commits"], 2);
+
+    // the shared root commit is counted once
+    let json = util::wait_for_json(addr, "/upstream/owner/repo/json?branches=all").await;
+    assert_eq!(json["count"], 4);
+    assert_eq!(json["commits"], 3);
+    assert_eq!(json["head"], main_head.to_string());
+
+    let json = util::wait_for_json(addr, "/upstream/owner/repo/json?branch=*").await;
+    assert_eq!(json["count"], 4);
+    assert_eq!(json["commits"], 3);
+
+    // new commits on any branch are added
+    checkout(&repo, "release");
+    commit_file(&repo, &path, "release.txt", "fix a\nfix b\nfix c\n");
+    checkout(&repo, &default_branch);
+    let json = util::wait_for_commits(addr, "/upstream/owner/repo/json?branches=all", 4).await;
+    assert_eq!(json["count"], 5);
+
+    // commits of deleted branches are no longer counted
+    repo.find_branch("release", BranchType::Local)
+        .unwrap()
+        .delete()
+        .unwrap();
+    let json = util::wait_for_commits(addr, "/upstream/owner/repo/json?branches=all", 2).await;
+    assert_eq!(json["count"], 2);
+
+    handle.abort();
+}
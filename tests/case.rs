@@ -0,0 +1,57 @@
+mod util;
+
+use serde_json::{Value, json};
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn case_variants_share_repository() {
+    let upstream = tempdir().unwrap();
+    util::create_repo(
+        &upstream.path().join("owner").join("repo"),
+        &[("file.txt", "a\n")],
+    );
+
+    let clone_url = format!("file://{}/{{owner}}/{{repo}}", upstream.path().display());
+    let mut repodir = None;
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.platforms.push(
+            serde_json::from_value(json!({
+                "name": "upstream",
+                "domain": "upstream.test",
+                "clone_url": clone_url,
+                "case_insensitive": true,
+            }))
+            .unwrap(),
+        );
+        repodir = Some(settings.repodir.join("upstream.test"));
+    })
+    .await;
+    let repo_path = repodir.unwrap().join("owner").join("repo");
+
+    let json = util::wait_for_json(addr, "/upstream/owner/repo/json").await;
+    assert_eq!(json["count"], 1);
+
+    // served from the cache without another calculation
+    let body = reqwest::get(format!("http://{addr}/upstream/Owner/REPO/json"))
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["count"], 1);
+    assert!(repo_path.exists());
+
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .post(format!("http://{addr}/upstream/OWNER/Repo/delete"))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_redirection());
+    assert!(!repo_path.exists());
+
+    handle.abort();
+}
@@ -0,0 +1,89 @@
+mod util;
+
+use std::{
+    fs,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::Request,
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::IntoResponse,
+};
+use serde_json::{Value, json};
+use tempfile::tempdir;
+use tokio::net::TcpListener;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn revoked_credentials() {
+    let upstream = tempdir().unwrap();
+    let path = upstream.path().join("owner").join("repo");
+    let repo = util::create_repo(&path, &[("file.txt", "a\nb\n")]);
+
+    // rejects every request after the credentials were revoked
+    let revoked = Arc::new(AtomicBool::new(false));
+    let router = util::git_http_router(upstream.path().to_path_buf()).layer(middleware::from_fn({
+        let revoked = Arc::clone(&revoked);
+        move |request: Request, next: Next| {
+            let revoked = revoked.load(Ordering::SeqCst);
+            async move {
+                if revoked {
+                    (
+                        StatusCode::UNAUTHORIZED,
+                        [(header::WWW_AUTHENTICATE, "Basic realm=\"forge\"")],
+                    )
+                        .into_response()
+                } else {
+                    next.run(request).await
+                }
+            }
+        }
+    }));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let domain = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.platforms.push(
+            serde_json::from_value(json!({
+                "name": "forge",
+                "domain": domain,
+                "clone_url": "http://{domain}/{owner}/{repo}",
+            }))
+            .unwrap(),
+        );
+        settings.credentials.push(
+            serde_json::from_value(json!({
+                "platform": "forge",
+                "token": "secret",
+            }))
+            .unwrap(),
+        );
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/forge/owner/repo/json").await;
+    assert_eq!(json["count"], 2);
+
+    revoked.store(true, Ordering::SeqCst);
+    fs::write(path.join("file.txt"), "a\nb\nc\n").unwrap();
+    util::commit(&repo, "file.txt", "extend file");
+
+    // the stale count is no longer served
+    let mut json = Value::Null;
+    for _ in 0..100 {
+        json = util::wait_for_json(addr, "/forge/owner/repo/json").await;
+        if json["status"] == "unauthorized" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(json["status"], "unauthorized");
+
+    handle.abort();
+}
@@ -0,0 +1,49 @@
+mod util;
+
+use std::time::Duration;
+
+use serde_json::json;
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn default_branch_change_is_tracked() {
+    let upstream = tempdir().unwrap();
+    let repo_path = upstream.path().join("owner").join("repo");
+    let repo = util::create_repo(&repo_path, &[("file.txt", "a\n")]);
+
+    let clone_url = format!("file://{}/{{owner}}/{{repo}}", upstream.path().display());
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.platforms.push(
+            serde_json::from_value(json!({
+                "name": "upstream",
+                "domain": "upstream.test",
+                "clone_url": clone_url,
+            }))
+            .unwrap(),
+        );
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/upstream/owner/repo/json").await;
+    assert_eq!(json["count"], 1);
+
+    // rename the default branch and add a commit
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    repo.branch("main", &head, false).unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    std::fs::write(repo_path.join("other.txt"), "b\nc\n").unwrap();
+    util::commit(&repo, "other.txt", "commit on main");
+
+    let mut count = json["count"].clone();
+    for _ in 0..100 {
+        let json = util::wait_for_json(addr, "/upstream/owner/repo/json").await;
+        count = json["count"].clone();
+        if count == 3 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(count, 3);
+
+    handle.abort();
+}
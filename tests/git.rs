@@ -0,0 +1,55 @@
+mod util;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+
+#[tokio::test]
+async fn git_url_on_allowed_host_succeeds() {
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.git_hosts.push("git.example.com".to_string());
+    })
+    .await;
+
+    let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+        .build_http();
+
+    let response = client
+        .request(
+            Request::builder()
+                .uri(format!(
+                    "http://{addr}/git/json?url=https%3A%2F%2Fgit.example.com%2Fgroup%2Frepo.git"
+                ))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .expect("Failed to execute request");
+
+    assert!(response.status().is_success());
+    handle.abort();
+}
+
+#[tokio::test]
+async fn git_url_on_unknown_host_fails() {
+    let (_test_app, handle, addr) = util::spawn_app().await;
+
+    let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+        .build_http();
+
+    let response = client
+        .request(
+            Request::builder()
+                .uri(format!(
+                    "http://{addr}/git/json?url=https%3A%2F%2Fgit.example.com%2Fgroup%2Frepo.git"
+                ))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    handle.abort();
+}
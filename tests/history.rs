@@ -0,0 +1,96 @@
+mod util;
+
+use std::fs;
+
+use jiff::Timestamp;
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn history_buckets() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    let repo = util::create_repo(&path, &[]);
+
+    // 2024-01-01 and 2024-01-03 are in the same week, 2024-01-15 two weeks later
+    let commits = [
+        ("2024-01-01T12:00:00Z", "a\n"),
+        ("2024-01-03T12:00:00Z", "a\nb\n"),
+        ("2024-01-15T12:00:00Z", "a\nb\nc\n"),
+    ];
+    for (time, content) in commits {
+        fs::write(path.join("file.txt"), content).unwrap();
+        let time: Timestamp = time.parse().unwrap();
+        util::commit_at(
+            &repo,
+            "file.txt",
+            time.to_string().as_str(),
+            time.as_second(),
+        );
+    }
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/history").await;
+    assert_eq!(json["bucket"], "week");
+    let history = json["history"].as_array().unwrap();
+    let weeks: Vec<_> = history
+        .iter()
+        .map(|point| {
+            (
+                point["date"].as_str().unwrap(),
+                point["count"].as_u64().unwrap(),
+                point["cumulative_count"].as_u64().unwrap(),
+                point["cumulative_commits"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        weeks,
+        vec![
+            ("2024-01-01", 2, 2, 2),
+            ("2024-01-08", 0, 2, 2),
+            ("2024-01-15", 1, 3, 3),
+        ]
+    );
+
+    let json = util::wait_for_json(
+        addr,
+        "/local/owner/repo/history?bucket=day&since=2024-01-02",
+    )
+    .await;
+    assert_eq!(json["bucket"], "day");
+    let history = json["history"].as_array().unwrap();
+    assert_eq!(history.len(), 13);
+    assert_eq!(history[0]["date"], "2024-01-03");
+    assert_eq!(history[12]["cumulative_count"], 2);
+
+    let response = reqwest::get(format!(
+        "http://{addr}/local/owner/repo/history?bucket=day&format=csv&until=2024-01-03"
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.headers()[reqwest::header::CONTENT_TYPE],
+        "text/csv; charset=utf-8"
+    );
+    assert_eq!(
+        response.text().await.unwrap(),
+        "date,count,additions,deletions,commits,cumulative_count,cumulative_commits\n\
+         2024-01-01,1,1,0,1,1,1\n\
+         2024-01-02,0,0,0,0,1,1\n\
+         2024-01-03,1,1,0,1,2,2\n"
+    );
+
+    let response = reqwest::get(format!(
+        "http://{addr}/local/owner/repo/history?bucket=year"
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    handle.abort();
+}
@@ -0,0 +1,57 @@
+mod util;
+
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn include_patterns() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    util::create_repo(
+        &path,
+        &[
+            ("README.md", "a\n"),
+            ("crates/foo/lib.rs", "fn a() {}\nfn b() {}\nfn c() {}\n"),
+            ("crates/foo/generated.rs", "1\n2\n"),
+            ("crates/bar/lib.rs", "x\ny\n"),
+        ],
+    );
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?include=crates/foo/**").await;
+    assert_eq!(json["count"], 5);
+
+    // excludes are applied to the included files
+    let json = util::wait_for_json(
+        addr,
+        "/local/owner/repo/json?include=crates/foo/**&exclude=crates/foo/generated.rs",
+    )
+    .await;
+    assert_eq!(json["count"], 3);
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 8);
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn generator_supports_includes() {
+    let (_test_app, handle, addr) = util::spawn_app().await;
+
+    let body = reqwest::get(format!(
+        "http://{addr}/generate?service=github&user=vbrandl&repo=hoc&include=crates%2Ffoo%2F**"
+    ))
+    .await
+    .unwrap()
+    .text()
+    .await
+    .unwrap();
+
+    assert!(body.contains("/github/vbrandl/hoc/view?include=crates/foo/**"));
+
+    handle.abort();
+}
@@ -0,0 +1,33 @@
+mod util;
+
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn cache_is_updated_from_cached_head() {
+    let root = tempdir().unwrap();
+    let repo_path = root.path().join("owner").join("repo");
+    let repo = util::create_repo(&repo_path, &[("file.txt", "a\n")]);
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["commits"], 1);
+
+    // only the new commit is counted
+    std::fs::write(repo_path.join("b.txt"), "b\nb\n").unwrap();
+    util::commit(&repo, "b.txt", "second");
+    let json = util::wait_for_commits(addr, "/local/owner/repo/json", 2).await;
+    assert_eq!(json["count"], 3);
+
+    // the head of the previous update is stored, so the second commit is not counted again
+    std::fs::write(repo_path.join("c.txt"), "c\nc\nc\n").unwrap();
+    util::commit(&repo, "c.txt", "third");
+    let json = util::wait_for_commits(addr, "/local/owner/repo/json", 3).await;
+    assert_eq!(json["count"], 6);
+
+    handle.abort();
+}
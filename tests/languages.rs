@@ -0,0 +1,42 @@
+mod util;
+
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn languages_breakdown() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    util::create_repo(
+        &path,
+        &[
+            ("src/main.rs", "fn main() {}\nfn a() {}\nfn b() {}\n"),
+            ("README.md", "# Title\n"),
+            ("Cargo.lock", "lock\nfile\n"),
+        ],
+    );
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/languages?exclude=Cargo.lock").await;
+    let languages = json["languages"].as_array().unwrap();
+    assert_eq!(languages.len(), 2);
+    assert_eq!(languages[0]["name"], "Rust");
+    assert_eq!(languages[0]["count"], 3);
+    assert_eq!(languages[1]["name"], "Markdown");
+    assert_eq!(languages[1]["count"], 1);
+
+    let overview = reqwest::get(format!(
+        "http://{addr}/local/owner/repo/view?exclude=Cargo.lock"
+    ))
+    .await
+    .unwrap()
+    .text()
+    .await
+    .unwrap();
+    assert!(overview.contains("width: 75.0%; background-color: #dea584;"));
+
+    handle.abort();
+}
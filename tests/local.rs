@@ -0,0 +1,147 @@
+mod util;
+
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn local_repository() {
+    let root = tempdir().unwrap();
+    util::create_repo(
+        &root.path().join("owner").join("repo"),
+        &[("file.txt", "a\nb\nc\n"), ("file.txt", "a\nx\nc\n")],
+    );
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+
+    assert_eq!(json["count"], 5);
+    assert_eq!(json["additions"], 4);
+    assert_eq!(json["deletions"], 1);
+    assert_eq!(json["commits"], 2);
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn missing_local_repository() {
+    let root = tempdir().unwrap();
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+
+    assert_eq!(json["status"], "repo not found");
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn legacy_cache_entry_is_recalculated() {
+    let root = tempdir().unwrap();
+    util::create_repo(
+        &root.path().join("owner").join("repo"),
+        &[("file.txt", "a\nb\nc\n")],
+    );
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+        // written before additions and deletions were stored separately
+        let cache_file = settings
+            .cachedir
+            .join("local/owner/repo/default_branch/cache.json");
+        std::fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        std::fs::write(
+            cache_file,
+            r#"{"Cached":{"head":"0000000000000000000000000000000000000000","count":42,"commits":7}}"#,
+        )
+        .unwrap();
+    })
+    .await;
+
+    let json = util::wait_for_commits(addr, "/local/owner/repo/json", 1).await;
+
+    assert_eq!(json["count"], 3);
+    assert_eq!(json["additions"], 3);
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn legacy_cache_entry_is_served() {
+    let root = tempdir().unwrap();
+    // without commits, so the entry is never recalculated
+    git2::Repository::init(root.path().join("owner").join("repo")).unwrap();
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+        let cache_file = settings
+            .cachedir
+            .join("local/owner/repo/default_branch/cache.json");
+        std::fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        std::fs::write(
+            cache_file,
+            r#"{"Cached":{"head":"0000000000000000000000000000000000000000","count":42,"commits":7}}"#,
+        )
+        .unwrap();
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 42);
+    assert_eq!(json["commits"], 7);
+
+    let badge = reqwest::get(format!("http://{addr}/local/owner/repo"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(badge.contains("42"));
+    let badge = reqwest::get(format!("http://{addr}/local/owner/repo?metric=additions"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(badge.contains("loading"));
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn cache_entry_without_later_fields_is_kept() {
+    let root = tempdir().unwrap();
+    let repo = util::create_repo(
+        &root.path().join("owner").join("repo"),
+        &[("file.txt", "a\nb\nc\n")],
+    );
+    let head = repo.head().unwrap().target().unwrap();
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+        // fields like `timeline` or `files` are missing
+        let cache_file = settings
+            .cachedir
+            .join("local/owner/repo/default_branch/cache.json");
+        std::fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        std::fs::write(
+            cache_file,
+            format!(r#"{{"Cached":{{"head":"{head}","additions":10,"deletions":2,"commits":1}}}}"#),
+        )
+        .unwrap();
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+
+    assert_eq!(json["count"], 12);
+    assert_eq!(json["commits"], 1);
+
+    handle.abort();
+}
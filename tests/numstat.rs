@@ -0,0 +1,222 @@
+mod util;
+
+use std::{fs, path::Path};
+
+use git2::Signature;
+use serde_json::json;
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn numstat_semantics() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    let lines: String = (1..=10).map(|i| format!("line {i}\n")).collect();
+    let repo = util::create_repo(&path, &[("a.txt", &lines)]);
+
+    // whitespace changes are ignored
+    fs::write(path.join("a.txt"), lines.replace("line 5", "line  5 ")).unwrap();
+    util::commit(&repo, "a.txt", "whitespace");
+
+    // binary files are not counted
+    fs::write(path.join("bin.dat"), [0u8, 1, 2, 3, 0, 255]).unwrap();
+    util::commit(&repo, "bin.dat", "binary");
+
+    // renames are not counted
+    fs::rename(path.join("a.txt"), path.join("b.txt")).unwrap();
+    let mut index = repo.index().unwrap();
+    index.remove_path(Path::new("a.txt")).unwrap();
+    index.write().unwrap();
+    util::commit(&repo, "b.txt", "rename");
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+
+    assert_eq!(json["count"], 10);
+    assert_eq!(json["commits"], 4);
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn copies() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    let lines: String = (1..=10).map(|i| format!("line {i}\n")).collect();
+    let repo = util::create_repo(&path, &[("a.txt", &lines)]);
+
+    // a copy only counts the changed lines
+    fs::write(
+        path.join("b.txt"),
+        format!("{}line 11\n", lines.replace("line 5\n", "changed 5\n")),
+    )
+    .unwrap();
+    util::commit(&repo, "b.txt", "copy");
+
+    // a new file that shares less than half of its content is no copy
+    let similar: String = (1..=4)
+        .map(|i| format!("line {i}\n"))
+        .chain((1..=6).map(|i| format!("other {i}\n")))
+        .collect();
+    fs::write(path.join("c.txt"), similar).unwrap();
+    util::commit(&repo, "c.txt", "similar");
+
+    // git does not ignore whitespace when looking for copies
+    fs::write(path.join("d.txt"), lines.replace("line", "    line")).unwrap();
+    util::commit(&repo, "d.txt", "indented");
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    // like `git log --numstat -w -M --find-copies-harder --diff-filter=ACDM`
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["additions"], 32);
+    assert_eq!(json["deletions"], 1);
+
+    handle.abort();
+}
+
+/// Create a repository with a whitespace change, a rename and a merge.
+fn create_semantics_repo(path: &Path) {
+    let lines: String = (1..=10).map(|i| format!("line {i}\n")).collect();
+    let repo = util::create_repo(path, &[("a.txt", &lines)]);
+
+    fs::write(path.join("a.txt"), lines.replace("line 5", "line  5 ")).unwrap();
+    util::commit(&repo, "a.txt", "whitespace");
+
+    fs::rename(path.join("a.txt"), path.join("b.txt")).unwrap();
+    let mut index = repo.index().unwrap();
+    index.remove_path(Path::new("a.txt")).unwrap();
+    index.write().unwrap();
+    let base = util::commit(&repo, "b.txt", "rename");
+
+    // a branch adding `c.txt`, merged into `HEAD`
+    fs::write(path.join("c.txt"), "x\ny\nz\n").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("c.txt")).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let base = repo.find_commit(base).unwrap();
+    let signature = Signature::now("Test", "test@example.com").unwrap();
+    let feature = repo
+        .commit(None, &signature, &signature, "feature", &tree, &[&base])
+        .unwrap();
+    let feature = repo.find_commit(feature).unwrap();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "merge",
+        &tree,
+        &[&base, &feature],
+    )
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn configurable_semantics() {
+    let root = tempdir().unwrap();
+    create_semantics_repo(&root.path().join("owner").join("repo"));
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 13);
+    assert_eq!(json["commits"], 5);
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?whitespace=count").await;
+    assert_eq!(json["count"], 15);
+
+    // the rename counts as deleted and added file
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?renames=off").await;
+    assert_eq!(json["count"], 33);
+
+    // the merge is diffed against the first parent, the branch is not walked
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?merges=first-parent").await;
+    assert_eq!(json["count"], 13);
+    assert_eq!(json["commits"], 4);
+
+    let response = reqwest::get(format!(
+        "http://{addr}/local/owner/repo/json?whitespace=sometimes"
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn configured_default_semantics() {
+    let root = tempdir().unwrap();
+    create_semantics_repo(&root.path().join("owner").join("repo"));
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+        settings.diff = serde_json::from_value(json!({ "whitespace": "count" })).unwrap();
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 15);
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?whitespace=ignore").await;
+    assert_eq!(json["count"], 13);
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn binary_files() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    let repo = util::create_repo(&path, &[("a.txt", "a\nb\nc\n")]);
+
+    fs::write(path.join("logo.png"), [0u8, 1, 2, 3, 0, 255]).unwrap();
+    util::commit(&repo, "logo.png", "add logo");
+    fs::write(path.join("logo.png"), [0u8, 4, 5, 6, 0, 255]).unwrap();
+    util::commit(&repo, "logo.png", "change logo");
+    fs::remove_file(path.join("logo.png")).unwrap();
+    let mut index = repo.index().unwrap();
+    index.remove_path(Path::new("logo.png")).unwrap();
+    index.write().unwrap();
+    util::commit(&repo, "a.txt", "delete logo");
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 3);
+    assert!(json.get("binary_changes").is_none());
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?binary=count").await;
+    assert_eq!(json["additions"], 5);
+    assert_eq!(json["deletions"], 1);
+
+    let json =
+        util::wait_for_json(addr, "/local/owner/repo/json?binary=count&binary_weight=10").await;
+    assert_eq!(json["count"], 33);
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?binary=report").await;
+    assert_eq!(json["count"], 3);
+    assert_eq!(json["binary_changes"], 3);
+
+    let response = reqwest::get(format!(
+        "http://{addr}/local/owner/repo/json?binary=count&binary_weight=10001"
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    handle.abort();
+}
@@ -0,0 +1,108 @@
+mod util;
+
+use axum::{body::Body, http::Request};
+use http_body_util::BodyExt;
+use serde_json::json;
+
+#[tokio::test]
+async fn generator_supports_configured_platform() {
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.platforms.push(
+            serde_json::from_value(json!({
+                "name": "example",
+                "domain": "git.example.com",
+            }))
+            .unwrap(),
+        );
+    })
+    .await;
+
+    let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+        .build_http();
+
+    let response = client
+        .request(
+            Request::builder()
+                .uri(format!(
+                    "http://{addr}/generate?service=example&user=vbrandl&repo=hoc"
+                ))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .expect("Failed to execute request");
+
+    assert!(response.status().is_success());
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(body.contains("https://git.example.com/vbrandl/hoc"));
+    assert!(body.contains("/example/vbrandl/hoc/view"));
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn generator_supports_codeberg() {
+    let (_test_app, handle, addr) = util::spawn_app().await;
+
+    let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+        .build_http();
+
+    let response = client
+        .request(
+            Request::builder()
+                .uri(format!(
+                    "http://{addr}/generate?service=codeberg&user=forgejo&repo=forgejo"
+                ))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .expect("Failed to execute request");
+
+    assert!(response.status().is_success());
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(body.contains("https://codeberg.org/forgejo/forgejo"));
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn overview_links_configured_web_url() {
+    let upstream = tempfile::tempdir().unwrap();
+    util::create_repo(
+        &upstream.path().join("owner").join("repo"),
+        &[("file.txt", "a\n")],
+    );
+
+    let clone_url = format!("file://{}/{{owner}}/{{repo}}", upstream.path().display());
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.platforms.push(
+            serde_json::from_value(json!({
+                "name": "upstream",
+                "domain": "upstream.test",
+                "clone_url": clone_url,
+                "web_url": "https://{domain}/git/{owner}/{repo}/tree",
+            }))
+            .unwrap(),
+        );
+    })
+    .await;
+
+    util::wait_for_json(addr, "/upstream/owner/repo/json").await;
+
+    let body = reqwest::get(format!("http://{addr}/upstream/owner/repo/view"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains("https://upstream.test/git/owner/repo/tree"));
+
+    handle.abort();
+}
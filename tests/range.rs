@@ -0,0 +1,129 @@
+mod util;
+
+use std::fs;
+
+use git2::{Repository, Signature};
+use serde_json::json;
+use tempfile::tempdir;
+
+fn commit_lines(repo: &Repository, path: &std::path::Path, lines: usize) -> git2::Oid {
+    let content: String = (0..lines).map(|i| format!("line {i}\n")).collect();
+    fs::write(path.join("file.txt"), content).unwrap();
+    util::commit(repo, "file.txt", &format!("{lines} lines"))
+}
+
+fn tag(repo: &Repository, name: &str, oid: git2::Oid) {
+    let object = repo.find_object(oid, None).unwrap();
+    let tagger = Signature::now("Test", "test@example.com").unwrap();
+    repo.tag(name, &object, &tagger, name, false).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn range_between_tags() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    let repo = util::create_repo(&path, &[]);
+    let v1 = commit_lines(&repo, &path, 1);
+    tag(&repo, "v1", v1);
+    let second = commit_lines(&repo, &path, 3);
+    let v2 = commit_lines(&repo, &path, 6);
+    repo.tag_lightweight("v2", &repo.find_object(v2, None).unwrap(), false)
+        .unwrap();
+    commit_lines(&repo, &path, 10);
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?from=v1&to=v2").await;
+    assert_eq!(json["count"], 5);
+    assert_eq!(json["commits"], 2);
+    assert_eq!(json["head"], v2.to_string());
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?from=v1").await;
+    assert_eq!(json["count"], 9);
+
+    let json = util::wait_for_json(addr, &format!("/local/owner/repo/json?to={second}")).await;
+    assert_eq!(json["count"], 3);
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 10);
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn tags_are_fetched() {
+    let upstream = tempdir().unwrap();
+    let path = upstream.path().join("owner").join("repo");
+    let repo = util::create_repo(&path, &[]);
+    commit_lines(&repo, &path, 2);
+
+    let clone_url = format!("file://{}/{{owner}}/{{repo}}", upstream.path().display());
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.platforms.push(
+            serde_json::from_value(json!({
+                "name": "upstream",
+                "domain": "upstream.test",
+                "clone_url": clone_url,
+            }))
+            .unwrap(),
+        );
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/upstream/owner/repo/json").await;
+    assert_eq!(json["count"], 2);
+
+    // tagged after the repository was cloned
+    let release = commit_lines(&repo, &path, 5);
+    tag(&repo, "release", release);
+    commit_lines(&repo, &path, 1);
+
+    let json = util::wait_for_json(addr, "/upstream/owner/repo/json?to=release").await;
+    assert_eq!(json["count"], 5);
+    assert_eq!(json["head"], release.to_string());
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn only_branches_tags_and_hashes_are_accepted() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    let repo = util::create_repo(&path, &[]);
+    let first = commit_lines(&repo, &path, 1);
+    tag(&repo, "v1", first);
+    commit_lines(&repo, &path, 4);
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?from=refs/tags/v1").await;
+    assert_eq!(json["count"], 3);
+
+    let abbreviated = &first.to_string()[..7];
+    let json = util::wait_for_json(addr, &format!("/local/owner/repo/json?to={abbreviated}")).await;
+    assert_eq!(json["count"], 1);
+
+    for query in [
+        "to=:/lines",
+        "from=master@%7B1%7D",
+        "to=HEAD~1",
+        "from=v1%5E",
+    ] {
+        let response = reqwest::get(format!("http://{addr}/local/owner/repo/json?{query}"))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::BAD_REQUEST,
+            "{query}"
+        );
+    }
+
+    handle.abort();
+}
@@ -0,0 +1,178 @@
+mod util;
+
+use std::path::{Path, PathBuf};
+
+use axum::{response::Redirect, routing::get};
+use git2::{Repository, build::RepoBuilder};
+use serde_json::{Value, json};
+use tempfile::tempdir;
+use tokio::net::TcpListener;
+
+/// Forge that moved `owner/old` to `owner/new`.
+async fn spawn_forge(root: PathBuf) -> String {
+    let router = util::git_http_router(root).route(
+        "/owner/old/info/refs",
+        get(|| async { Redirect::permanent("/owner/new/info/refs?service=git-upload-pack") }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    addr.to_string()
+}
+
+fn forge(domain: &str) -> Value {
+    json!({
+        "name": "forge",
+        "domain": domain,
+        "clone_url": "http://{domain}/{owner}/{repo}",
+    })
+}
+
+fn assert_moved(repodir: &Path, domain: &str) {
+    assert!(!repodir.join("owner").join("old").exists());
+    let repo = Repository::open_bare(repodir.join("owner").join("new")).unwrap();
+    assert_eq!(
+        repo.find_remote("origin").unwrap().url().unwrap(),
+        format!("http://{domain}/owner/new")
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn renamed_repository() {
+    let upstream = tempdir().unwrap();
+    util::create_repo(
+        &upstream.path().join("owner").join("new"),
+        &[("file.txt", "a\nb\n")],
+    );
+
+    let domain = spawn_forge(upstream.path().to_path_buf()).await;
+    let mut repodir = None;
+    let mut cachedir = None;
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings
+            .platforms
+            .push(serde_json::from_value(forge(&domain)).unwrap());
+        repodir = Some(settings.repodir.join(&domain));
+        cachedir = Some(settings.cachedir.join(&domain));
+    })
+    .await;
+    let repodir = repodir.unwrap();
+    let alias_file = cachedir
+        .unwrap()
+        .join("owner")
+        .join("old")
+        .join("alias.json");
+
+    // the repository is cloned at its new location
+    let json = util::wait_for_json(addr, "/forge/owner/old/json").await;
+    assert_eq!(json["count"], 2);
+    assert_moved(&repodir, &domain);
+
+    // both URLs share the cache entry
+    let json = util::wait_for_json(addr, "/forge/owner/new/json").await;
+    assert_eq!(json["count"], 2);
+
+    let overview = reqwest::get(format!("http://{addr}/forge/owner/old/view"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(overview.contains(&format!("https://{domain}/owner/new")));
+    assert!(alias_file.exists());
+
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .post(format!("http://{addr}/forge/owner/old/delete"))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_redirection());
+    assert!(!alias_file.exists());
+    assert!(!repodir.join("owner").join("new").exists());
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn expired_alias() {
+    let upstream = tempdir().unwrap();
+    util::create_repo(
+        &upstream.path().join("owner").join("new"),
+        &[("file.txt", "a\nb\n")],
+    );
+    // a new repository was created at the old location
+    util::create_repo(
+        &upstream.path().join("owner").join("old"),
+        &[("file.txt", "a\n")],
+    );
+
+    let router = util::git_http_router(upstream.path().to_path_buf());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let domain = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let mut alias_file = None;
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings
+            .platforms
+            .push(serde_json::from_value(forge(&domain)).unwrap());
+        // recorded by a version that did not check aliases again
+        let path = settings
+            .cachedir
+            .join(&domain)
+            .join("owner")
+            .join("old")
+            .join("alias.json");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, r#"{"owner":"owner","repo":"new"}"#).unwrap();
+        alias_file = Some(path);
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/forge/owner/old/json").await;
+    assert_eq!(json["count"], 1);
+    assert!(!alias_file.unwrap().exists());
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn moved_after_failed_fetch() {
+    let upstream = tempdir().unwrap();
+    let upstream_path = upstream.path().join("owner").join("new");
+    util::create_repo(&upstream_path, &[("file.txt", "a\nb\n")]);
+
+    let domain = spawn_forge(upstream.path().to_path_buf()).await;
+    let mut repodir = None;
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings
+            .platforms
+            .push(serde_json::from_value(forge(&domain)).unwrap());
+        // the repository was cloned before it was moved and can no longer be fetched from the
+        // old location
+        let repo = RepoBuilder::new()
+            .bare(true)
+            .clone(
+                &format!("file://{}", upstream_path.display()),
+                &settings.repodir.join(&domain).join("owner").join("old"),
+            )
+            .unwrap();
+        repo.remote_set_url(
+            "origin",
+            &format!("file://{}", upstream.path().join("owner/old").display()),
+        )
+        .unwrap();
+        repodir = Some(settings.repodir.join(&domain));
+    })
+    .await;
+    let repodir = repodir.unwrap();
+
+    let json = util::wait_for_json(addr, "/forge/owner/old/json").await;
+    assert_eq!(json["count"], 2);
+    assert_moved(&repodir, &domain);
+
+    handle.abort();
+}
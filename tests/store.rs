@@ -0,0 +1,63 @@
+mod util;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tempfile::tempdir;
+
+fn find(dir: &Path, name: &str) -> Option<PathBuf> {
+    for entry in fs::read_dir(dir).ok()? {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            if let Some(found) = find(&path, name) {
+                return Some(found);
+            }
+        } else if path.file_name().is_some_and(|n| n == name) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn variants_reuse_stored_numstat() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    let repo = util::create_repo(
+        &path,
+        &[
+            ("a.txt", "a\n"),
+            ("b.txt", "x\ny\n"),
+            ("Cargo.lock", "lock\nfile\nlines\n"),
+        ],
+    );
+
+    let mut cachedir = None;
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+        cachedir = Some(settings.cachedir.clone());
+    })
+    .await;
+    let cachedir = cachedir.unwrap();
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 6);
+    let store = find(&cachedir, "numstat.jsonl").expect("numstat store is written");
+    assert_eq!(fs::read_to_string(&store).unwrap().lines().count(), 3);
+
+    // another variant is computed from the stored commits
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?exclude=Cargo.lock").await;
+    assert_eq!(json["count"], 3);
+    assert_eq!(fs::read_to_string(&store).unwrap().lines().count(), 3);
+
+    // new commits are appended
+    fs::write(path.join("a.txt"), "").unwrap();
+    util::commit(&repo, "a.txt", "empty");
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?include=a.txt").await;
+    assert_eq!(json["count"], 2);
+    assert_eq!(fs::read_to_string(&store).unwrap().lines().count(), 4);
+
+    handle.abort();
+}
@@ -0,0 +1,147 @@
+mod util;
+
+use std::{fs, path::Path};
+
+use git2::{IndexEntry, IndexTime, Oid, Repository};
+use tempfile::tempdir;
+
+/// Pin the submodule `name` to `commit` and commit the change.
+///
+/// The submodule is the sibling repository `../<name>` and the only one in `.gitmodules`.
+fn pin_submodule(repo: &Repository, path: &Path, name: &str, commit: Oid, message: &str) {
+    fs::write(
+        path.join(".gitmodules"),
+        format!("[submodule \"{name}\"]\n\tpath = {name}\n\turl = ../{name}\n"),
+    )
+    .unwrap();
+    let mut index = repo.index().unwrap();
+    index
+        .add(&IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o160_000,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id: commit,
+            flags: 3,
+            flags_extended: 0,
+            path: name.as_bytes().to_vec(),
+        })
+        .unwrap();
+    index.write().unwrap();
+    util::commit(repo, ".gitmodules", message);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn recurse_submodules() {
+    let root = tempdir().unwrap();
+    let lib_path = root.path().join("owner").join("lib");
+    let lib = util::create_repo(&lib_path, &[("lib.txt", "fn a\nfn b\n")]);
+    let first = lib.head().unwrap().target().unwrap();
+    fs::write(lib_path.join("lib.txt"), "fn a\nfn b\nfn c\n").unwrap();
+    let second = util::commit(&lib, "lib.txt", "extend lib");
+
+    let path = root.path().join("owner").join("parent");
+    let repo = util::create_repo(&path, &[("main.txt", "main\n")]);
+    // `.gitmodules` has 3 lines
+    pin_submodule(&repo, &path, "lib", first, "add lib");
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/parent/json").await;
+    assert_eq!(json["count"], 4);
+    assert_eq!(json["commits"], 2);
+    assert!(json.get("submodules").is_none());
+
+    // the submodule is counted from the commit it was first pinned to
+    let json = util::wait_for_json(addr, "/local/owner/parent/json?submodules=recurse").await;
+    assert_eq!(json["count"], 4);
+    assert_eq!(json["commits"], 2);
+    assert_eq!(json["submodules"][0]["path"], "lib");
+    assert_eq!(json["submodules"][0]["url"], "../lib");
+    assert_eq!(json["submodules"][0]["head"], first.to_string());
+    assert_eq!(json["submodules"][0]["count"], 0);
+    assert_eq!(json["submodules"][0]["commits"], 0);
+
+    // only the new commits of the submodule are added
+    pin_submodule(&repo, &path, "lib", second, "update lib");
+    let json = util::wait_for_commits(addr, "/local/owner/parent/json?submodules=recurse", 4).await;
+    assert_eq!(json["count"], 5);
+    assert_eq!(json["submodules"][0]["head"], second.to_string());
+    assert_eq!(json["submodules"][0]["count"], 1);
+    assert_eq!(json["submodules"][0]["commits"], 1);
+
+    let json = util::wait_for_json(addr, "/local/owner/parent/tree?submodules=recurse").await;
+    assert!(
+        json["directories"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|dir| dir["path"] == "lib" && dir["count"] == 1)
+    );
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn nested_submodules() {
+    let root = tempdir().unwrap();
+    let inner_path = root.path().join("owner").join("inner");
+    let inner = util::create_repo(&inner_path, &[("inner.txt", "a\nb\n")]);
+    let first_inner = inner.head().unwrap().target().unwrap();
+
+    let lib_path = root.path().join("owner").join("lib");
+    let lib = util::create_repo(&lib_path, &[("lib.txt", "fn a\n")]);
+    pin_submodule(&lib, &lib_path, "inner", first_inner, "add inner");
+    let first_lib = lib.head().unwrap().target().unwrap();
+
+    let path = root.path().join("owner").join("parent");
+    let repo = util::create_repo(&path, &[("main.txt", "main\n")]);
+    pin_submodule(&repo, &path, "lib", first_lib, "add lib");
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/parent/json?submodules=recurse").await;
+    assert_eq!(json["count"], 4);
+    assert_eq!(json["commits"], 2);
+    assert_eq!(json["submodules"][1]["path"], "lib/inner");
+    assert_eq!(json["submodules"][1]["url"], "../inner");
+    assert_eq!(json["submodules"][1]["head"], first_inner.to_string());
+    assert_eq!(json["submodules"][1]["count"], 0);
+
+    // the new pin of the nested submodule is followed through the new pin of `lib`
+    fs::write(inner_path.join("inner.txt"), "a\nb\nc\n").unwrap();
+    let second_inner = util::commit(&inner, "inner.txt", "extend inner");
+    pin_submodule(&lib, &lib_path, "inner", second_inner, "update inner");
+    let second_lib = lib.head().unwrap().target().unwrap();
+    pin_submodule(&repo, &path, "lib", second_lib, "update lib");
+
+    let json = util::wait_for_commits(addr, "/local/owner/parent/json?submodules=recurse", 5).await;
+    assert_eq!(json["count"], 5);
+    assert_eq!(json["submodules"][0]["path"], "lib");
+    assert_eq!(json["submodules"][0]["count"], 0);
+    assert_eq!(json["submodules"][0]["commits"], 1);
+    assert_eq!(json["submodules"][1]["head"], second_inner.to_string());
+    assert_eq!(json["submodules"][1]["count"], 1);
+    assert_eq!(json["submodules"][1]["commits"], 1);
+
+    // a fresh count starts at the first pins as well
+    let json = util::wait_for_json(
+        addr,
+        "/local/owner/parent/json?submodules=recurse&renames=off",
+    )
+    .await;
+    assert_eq!(json["count"], 5);
+    assert_eq!(json["commits"], 5);
+
+    handle.abort();
+}
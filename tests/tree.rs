@@ -0,0 +1,62 @@
+mod util;
+
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn tree_by_directory() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    util::create_repo(
+        &path,
+        &[
+            ("README.md", "a\n"),
+            ("crates/a/lib.rs", "fn a() {}\nfn b() {}\nfn c() {}\n"),
+            ("crates/b/lib.rs", "x\ny\n"),
+            ("vendor/dep.rs", "1\n2\n3\n4\n"),
+        ],
+    );
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/tree?exclude=vendor/").await;
+    assert_eq!(json["depth"], 1);
+    let directories = json["directories"].as_array().unwrap();
+    assert_eq!(directories.len(), 2);
+    assert_eq!(directories[0]["path"], "crates");
+    assert_eq!(directories[0]["count"], 5);
+    assert_eq!(directories[1]["path"], ".");
+    assert_eq!(directories[1]["count"], 1);
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/tree?exclude=vendor/&depth=2").await;
+    assert_eq!(json["directories"][0]["path"], "crates/a");
+    assert_eq!(json["directories"][0]["count"], 3);
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn tree_after_badge() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    util::create_repo(
+        &path,
+        &[("README.md", "a\n"), ("src/lib.rs", "fn a() {}\n")],
+    );
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    // the entry of the badge does not keep the files, so they are counted again
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 2);
+    let json = util::wait_for_json(addr, "/local/owner/repo/tree").await;
+    assert_eq!(json["directories"][0]["path"], ".");
+    assert_eq!(json["directories"][1]["path"], "src");
+
+    handle.abort();
+}
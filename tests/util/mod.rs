@@ -8,9 +8,22 @@ use hoc::{
 
 use std::{
     net::SocketAddr,
+    path::{Path, PathBuf},
     sync::{Arc, LazyLock, atomic::AtomicUsize},
+    time::Duration,
 };
 
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    extract::{Path as UrlPath, State},
+    http::{Request, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use git2::{Buf, Oid, Repository, Signature, Time};
+use http_body_util::BodyExt;
+use serde_json::Value;
 use tempfile::{TempDir, tempdir};
 use tokio::task::JoinHandle;
 
@@ -30,7 +43,14 @@ pub struct TestApp {
     _cache_dir: TempDir,
 }
 
+#[allow(dead_code)]
 pub async fn spawn_app() -> (TestApp, JoinHandle<()>, SocketAddr) {
+    spawn_app_with(|_| ()).await
+}
+
+pub async fn spawn_app_with(
+    configure: impl FnOnce(&mut Settings),
+) -> (TestApp, JoinHandle<()>, SocketAddr) {
     LazyLock::force(&TRACING);
 
     let repo_dir = tempdir().expect("Cannot create repo_dir");
@@ -40,6 +60,7 @@ pub async fn spawn_app() -> (TestApp, JoinHandle<()>, SocketAddr) {
     settings.port = 0;
     settings.repodir = repo_dir.path().to_path_buf();
     settings.cachedir = cache_dir.path().to_path_buf();
+    configure(&mut settings);
 
     let queue = Queue::new();
     let cache = Persist::new(settings.clone());
@@ -51,6 +72,7 @@ pub async fn spawn_app() -> (TestApp, JoinHandle<()>, SocketAddr) {
         repo_count: AtomicUsize::new(0),
         cache,
         queue,
+        client: http::client().unwrap(),
     });
 
     let app = http::router(state).into_make_service_with_connect_info::<SocketAddr>();
@@ -65,3 +87,172 @@ pub async fn spawn_app() -> (TestApp, JoinHandle<()>, SocketAddr) {
         addr,
     )
 }
+
+/// Create a repository at `path` with one commit per entry, each writing `content` to `file`.
+#[allow(dead_code)]
+pub fn create_repo(path: &Path, commits: &[(&str, &str)]) -> Repository {
+    let repo = Repository::init(path).expect("Cannot create repository");
+    for (i, (file, content)) in commits.iter().enumerate() {
+        let file_path = path.join(file);
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(file_path, content).unwrap();
+        commit(&repo, file, &format!("commit {i}"));
+    }
+    repo
+}
+
+/// Stage `file` and commit it on top of `HEAD`.
+#[allow(dead_code)]
+pub fn commit(repo: &Repository, file: &str, message: &str) -> Oid {
+    commit_as(repo, file, message, "Test", "test@example.com")
+}
+
+/// Stage `file` and commit it on top of `HEAD` as the given author.
+#[allow(dead_code)]
+pub fn commit_as(repo: &Repository, file: &str, message: &str, name: &str, email: &str) -> Oid {
+    commit_with(repo, file, message, &Signature::now(name, email).unwrap())
+}
+
+/// Stage `file` and commit it on top of `HEAD` at `time` (seconds since the epoch).
+#[allow(dead_code)]
+pub fn commit_at(repo: &Repository, file: &str, message: &str, time: i64) -> Oid {
+    let signature = Signature::new("Test", "test@example.com", &Time::new(time, 0)).unwrap();
+    commit_with(repo, file, message, &signature)
+}
+
+fn commit_with(repo: &Repository, file: &str, message: &str, signature: &Signature) -> Oid {
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(file)).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let parents: Vec<_> = parent.iter().collect();
+    repo.commit(Some("HEAD"), signature, signature, message, &tree, &parents)
+        .unwrap()
+}
+
+/// Request `path` until the calculation is done and return the JSON body.
+#[allow(dead_code)]
+pub async fn wait_for_json(addr: SocketAddr, path: &str) -> Value {
+    let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+        .build_http();
+
+    for _ in 0..100 {
+        let response = client
+            .request(
+                Request::builder()
+                    .uri(format!("http://{addr}{path}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to execute request");
+        assert!(response.status().is_success());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        if json["status"] != "loading" {
+            return json;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("calculation did not finish");
+}
+
+/// Request `path` until the given number of commits is counted, e.g. after new commits were
+/// added to a repository that was already counted.
+#[allow(dead_code)]
+pub async fn wait_for_commits(addr: SocketAddr, path: &str, commits: u64) -> Value {
+    for _ in 0..100 {
+        let json = wait_for_json(addr, path).await;
+        if json["commits"] == commits {
+            return json;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("calculation did not finish");
+}
+
+/// Serve the repositories below `root` at `/<owner>/<repo>` using the smart HTTP protocol, so
+/// they can be cloned and fetched like from a forge.
+///
+/// Only what libgit2 needs is implemented: no capabilities are advertised and the pack always
+/// contains every object reachable from the requested commits.
+#[allow(dead_code)]
+pub fn git_http_router(root: PathBuf) -> Router {
+    Router::new()
+        .route("/{owner}/{repo}/info/refs", get(advertise_refs))
+        .route("/{owner}/{repo}/git-upload-pack", post(upload_pack))
+        .with_state(Arc::new(root))
+}
+
+fn pkt_line(line: &str) -> String {
+    format!("{:04x}{line}", line.len() + 4)
+}
+
+async fn advertise_refs(
+    State(root): State<Arc<PathBuf>>,
+    UrlPath((owner, repo)): UrlPath<(String, String)>,
+) -> Response {
+    let Ok(repo) = Repository::open(root.join(owner).join(repo)) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let head = repo.head().unwrap();
+    let mut refs = vec![(head.target().unwrap(), "HEAD".to_string())];
+    for reference in repo.references().unwrap() {
+        let reference = reference.unwrap();
+        if let (Ok(name), Some(target)) = (reference.name(), reference.target())
+            && (name.starts_with("refs/heads/") || name.starts_with("refs/tags/"))
+        {
+            refs.push((target, name.to_string()));
+        }
+    }
+
+    let mut body = pkt_line("# service=git-upload-pack\n");
+    body.push_str("0000");
+    for (idx, (target, name)) in refs.iter().enumerate() {
+        if idx == 0 {
+            let symref = format!("symref=HEAD:{}", head.name().unwrap());
+            body.push_str(&pkt_line(&format!("{target} {name}\0{symref}\n")));
+        } else {
+            body.push_str(&pkt_line(&format!("{target} {name}\n")));
+        }
+    }
+    body.push_str("0000");
+    (
+        [(
+            header::CONTENT_TYPE,
+            "application/x-git-upload-pack-advertisement",
+        )],
+        body,
+    )
+        .into_response()
+}
+
+async fn upload_pack(
+    State(root): State<Arc<PathBuf>>,
+    UrlPath((owner, repo)): UrlPath<(String, String)>,
+    request: Bytes,
+) -> Response {
+    let content_type = [(header::CONTENT_TYPE, "application/x-git-upload-pack-result")];
+    let request = String::from_utf8_lossy(&request);
+    let mut response = b"0008NAK\n".to_vec();
+    // keep negotiating until the client is done sending the commits it has
+    if !request.contains("0009done\n") {
+        return (content_type, response).into_response();
+    }
+
+    let repo = Repository::open(root.join(owner).join(repo)).unwrap();
+    let mut walk = repo.revwalk().unwrap();
+    for (idx, _) in request.match_indices("want ") {
+        walk.push(Oid::from_str(&request[idx + 5..idx + 45]).unwrap())
+            .unwrap();
+    }
+    let mut builder = repo.packbuilder().unwrap();
+    builder.insert_walk(&mut walk).unwrap();
+    let mut pack = Buf::new();
+    builder.write_buf(&mut pack).unwrap();
+    response.extend_from_slice(&pack);
+    (content_type, response).into_response()
+}
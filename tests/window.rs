@@ -0,0 +1,61 @@
+mod util;
+
+use std::fs;
+
+use jiff::{SignedDuration, Timestamp};
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn time_windows() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("owner").join("repo");
+    let repo = util::create_repo(&path, &[]);
+
+    let commits = [
+        ("2024-01-15T12:00:00Z", "a\n"),
+        ("2024-02-15T12:00:00Z", "a\nb\n"),
+        ("2024-03-15T12:00:00Z", "a\nb\nc\n"),
+    ];
+    for (time, content) in commits {
+        fs::write(path.join("file.txt"), content).unwrap();
+        let time: Timestamp = time.parse().unwrap();
+        util::commit_at(
+            &repo,
+            "file.txt",
+            time.to_string().as_str(),
+            time.as_second(),
+        );
+    }
+    let recent = Timestamp::now() - SignedDuration::from_hours(24);
+    fs::write(path.join("file.txt"), "x\n").unwrap();
+    util::commit_at(&repo, "file.txt", "recent", recent.as_second());
+
+    let (_test_app, handle, addr) = util::spawn_app_with(|settings| {
+        settings.local_roots.push(root.path().to_path_buf());
+    })
+    .await;
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json").await;
+    assert_eq!(json["count"], 7);
+    assert_eq!(json["commits"], 4);
+
+    let json = util::wait_for_json(
+        addr,
+        "/local/owner/repo/json?since=2024-02-01&until=2024-03-15",
+    )
+    .await;
+    assert_eq!(json["count"], 2);
+    assert_eq!(json["commits"], 2);
+
+    let json = util::wait_for_json(addr, "/local/owner/repo/json?last=30d").await;
+    assert_eq!(json["additions"], 1);
+    assert_eq!(json["deletions"], 3);
+    assert_eq!(json["commits"], 1);
+
+    let response = reqwest::get(format!("http://{addr}/local/owner/repo/json?last=soon"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    handle.abort();
+}